@@ -25,10 +25,11 @@ import (
 )
 
 type Config struct {
-	Port            string
-	CacheServiceURL string
-	APIKey          string
-	RateLimit       string
+	Port               string
+	CacheServiceURL    string
+	APIKey             string
+	RateLimit          string
+	CacheServiceAPIKey string
 }
 
 type Response struct {
@@ -102,10 +103,11 @@ func main() {
 	logger.SetLevel(logrus.InfoLevel)
 
 	config := Config{
-		Port:            getEnv("PORT", "8080"),
-		CacheServiceURL: getEnv("CACHE_SERVICE_URL", "http://localhost:8081"),
-		APIKey:          getEnv("API_KEY", "your-secret-key"),
-		RateLimit:       getEnv("RATE_LIMIT", "100-M"),
+		Port:               getEnv("PORT", "8080"),
+		CacheServiceURL:    getEnv("CACHE_SERVICE_URL", "http://localhost:8081"),
+		APIKey:             getEnv("API_KEY", "your-secret-key"),
+		RateLimit:          getEnv("RATE_LIMIT", "100-M"),
+		CacheServiceAPIKey: getEnv("CACHE_SERVICE_API_KEY", ""),
 	}
 
 	// Initialize router
@@ -239,6 +241,111 @@ func respondWithJSON(w http.ResponseWriter, data interface{}, statusCode int) {
 	})
 }
 
+// forwardTraceContext copies the W3C trace context headers of the incoming
+// request so the cache service continues the caller's trace.
+func forwardTraceContext(dst, src *http.Request) {
+	for _, header := range []string{"traceparent", "tracestate"} {
+		if value := src.Header.Get(header); value != "" {
+			dst.Header.Set(header, value)
+		}
+	}
+}
+
+// authorizeCacheRequest authenticates a forwarded request to the cache service
+// with the gateway's own API key; callers' credentials are not passed on.
+func authorizeCacheRequest(req *http.Request, config Config) {
+	if config.CacheServiceAPIKey != "" {
+		req.Header.Set("Authorization", "Bearer "+config.CacheServiceAPIKey)
+	}
+}
+
+// forwardPreconditions copies the conditional request headers used for
+// revalidating reads and for set-if-absent and compare-and-swap writes.
+func forwardPreconditions(dst, src *http.Request) {
+	for _, header := range []string{"If-Match", "If-None-Match"} {
+		if value := src.Header.Get(header); value != "" {
+			dst.Header.Set(header, value)
+		}
+	}
+}
+
+// copyCacheHeaders copies the validator and caching headers of a cache
+// service response so clients downstream can revalidate their copies.
+func copyCacheHeaders(w http.ResponseWriter, resp *http.Response) {
+	for _, header := range []string{"ETag", "Cache-Control", "Age", "X-Cache"} {
+		if value := resp.Header.Get(header); value != "" {
+			w.Header().Set(header, value)
+		}
+	}
+}
+
+// proxyToCache forwards r to path on the cache service through the circuit
+// breaker, with its query, body, content type, trace context and
+// preconditions, and relays the response with its caching headers.
+func proxyToCache(w http.ResponseWriter, r *http.Request, config Config, path string) {
+	cacheURL := config.CacheServiceURL + path
+	if r.URL.RawQuery != "" {
+		cacheURL += "?" + r.URL.RawQuery
+	}
+	// Tied to the caller's request so that event streams end when it leaves
+	req, err := http.NewRequestWithContext(r.Context(), r.Method, cacheURL, r.Body)
+	if err != nil {
+		respondWithError(w, "Failed to create request", http.StatusInternalServerError)
+		return
+	}
+	req.ContentLength = r.ContentLength
+	if contentType := r.Header.Get("Content-Type"); contentType != "" {
+		req.Header.Set("Content-Type", contentType)
+	}
+	forwardTraceContext(req, r)
+	authorizeCacheRequest(req, config)
+	forwardPreconditions(req, r)
+	result, err := cacheBreaker.Execute(func() (interface{}, error) {
+		return http.DefaultClient.Do(req)
+	})
+	if err != nil {
+		respondWithError(w, "Failed to access cache", http.StatusInternalServerError)
+		return
+	}
+	resp := result.(*http.Response)
+	defer resp.Body.Close()
+
+	contentType := resp.Header.Get("Content-Type")
+	w.Header().Set("Content-Type", contentType)
+	copyCacheHeaders(w, resp)
+	// Invalidation job locations point into the cache service's paths
+	if location := resp.Header.Get("Location"); strings.HasPrefix(location, "/") {
+		w.Header().Set("Location", "/api"+location)
+	}
+	w.WriteHeader(resp.StatusCode)
+	if strings.HasPrefix(contentType, "text/event-stream") {
+		streamEvents(w, resp)
+		return
+	}
+	io.Copy(w, resp.Body)
+}
+
+// streamEvents relays a server-sent event stream, flushing each chunk as it
+// arrives. The server's write timeout is lifted since the stream stays open
+// until either side closes it.
+func streamEvents(w http.ResponseWriter, resp *http.Response) {
+	controller := http.NewResponseController(w)
+	controller.SetWriteDeadline(time.Time{})
+	buf := make([]byte, 4096)
+	for {
+		n, err := resp.Body.Read(buf)
+		if n > 0 {
+			if _, writeErr := w.Write(buf[:n]); writeErr != nil {
+				return
+			}
+			controller.Flush()
+		}
+		if err != nil {
+			return
+		}
+	}
+}
+
 func broadcastStateChange(from, to gobreaker.State) {
 	logger.Printf("Broadcasting state change from %v to %v", from, to)
 	message := map[string]interface{}{
@@ -261,7 +368,8 @@ func broadcastStateChange(from, to gobreaker.State) {
 }
 
 func setupRouter(config Config, limiter *limiter.Limiter) http.Handler {
-	r := mux.NewRouter()
+	// Routes match the escaped path so that keys may hold encoded slashes
+	r := mux.NewRouter().UseEncodedPath()
 
 	// Root path redirects to monitor dashboard
 	r.HandleFunc("/", func(w http.ResponseWriter, r *http.Request) {
@@ -355,50 +463,28 @@ func setupRouter(config Config, limiter *limiter.Limiter) http.Handler {
 		respondWithJSON(w, map[string]string{"message": "This is a protected endpoint"}, http.StatusOK)
 	}).Methods("GET")
 
-	// Cache endpoints
-	api.HandleFunc("/cache/{key}", func(w http.ResponseWriter, r *http.Request) {
-		vars := mux.Vars(r)
-		key := vars["key"]
-
-		// Forward request to cache service
-		cacheURL := config.CacheServiceURL + "/cache/" + key
-		req, err := http.NewRequest("GET", cacheURL, nil)
-		if err != nil {
-			respondWithError(w, "Failed to create request", http.StatusInternalServerError)
-			return
-		}
-		result, err := cacheBreaker.Execute(func() (interface{}, error) {
-			return http.DefaultClient.Do(req)
-		})
-		if err != nil {
-			respondWithError(w, "Failed to get cache", http.StatusInternalServerError)
-			return
-		}
-		resp := result.(*http.Response)
-		defer resp.Body.Close()
-
-		w.Header().Set("Content-Type", resp.Header.Get("Content-Type"))
-		w.WriteHeader(resp.StatusCode)
-		io.Copy(w, resp.Body)
-	}).Methods("GET")
-
-	api.HandleFunc("/cache", func(w http.ResponseWriter, r *http.Request) {
-		// Forward request to cache service
-		cacheURL := config.CacheServiceURL + "/cache"
-		result, err := cacheBreaker.Execute(func() (interface{}, error) {
-			return http.Post(cacheURL, "application/json", r.Body)
-		})
-		if err != nil {
-			respondWithError(w, "Failed to set cache", http.StatusInternalServerError)
-			return
-		}
-		resp := result.(*http.Response)
-		defer resp.Body.Close()
-
-		w.Header().Set("Content-Type", resp.Header.Get("Content-Type"))
-		w.WriteHeader(resp.StatusCode)
-		io.Copy(w, resp.Body)
-	}).Methods("POST")
+	// Cache endpoints, forwarded to the same path on the cache service
+	proxy := func(w http.ResponseWriter, r *http.Request) {
+		// The escaped path keeps keys such as "a%2Fb" in one segment
+		proxyToCache(w, r, config, strings.TrimPrefix(r.URL.EscapedPath(), "/api"))
+	}
+	// Endpoints taking JSON bodies are forwarded as JSON whatever the
+	// caller's content type.
+	proxyJSON := func(w http.ResponseWriter, r *http.Request) {
+		r.Header.Set("Content-Type", "application/json")
+		proxy(w, r)
+	}
+	// The default namespace at the root, and every namespace under /ns
+	for _, prefix := range []string{"", "/ns/{namespace}"} {
+		api.HandleFunc(prefix+"/cache/{key}", proxy).Methods("GET", "PUT", "DELETE")
+		api.HandleFunc(prefix+"/cache", proxyJSON).Methods("GET", "POST", "DELETE")
+		api.HandleFunc(prefix+"/cache/batch/{op:get|set}", proxyJSON).Methods("POST")
+		api.HandleFunc(prefix+"/cache/{key}/ttl", proxyJSON).Methods("GET", "PUT", "DELETE")
+		api.HandleFunc(prefix+"/cache/{key}/{op:incr|decr}", proxyJSON).Methods("POST")
+		api.HandleFunc(prefix+"/invalidations", proxyJSON).Methods("GET", "POST")
+		api.HandleFunc(prefix+"/invalidations/{id}", proxy).Methods("GET")
+		api.HandleFunc(prefix+"/events", proxy).Methods("GET")
+	}
 
 	// Add WebSocket endpoint for monitoring
 	r.HandleFunc("/ws", func(w http.ResponseWriter, r *http.Request) {
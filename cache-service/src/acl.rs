@@ -0,0 +1,402 @@
+//! Authorization of authenticated callers.
+//!
+//! A policy file grants identities operations on the keys under some
+//! prefixes. Prefixes match keys as stored, so `ns:billing:` covers the whole
+//! `billing` namespace. Deny rules take precedence over allow rules, and
+//! anything no rule allows is denied.
+
+use crate::auth::Identity;
+use crate::error::ApiError;
+use crate::namespace::Namespace;
+use crate::AppState;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+/// What a request does with the keys it addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Read,
+    Write,
+    Delete,
+    /// Use of the `/admin/*` endpoints; not tied to keys.
+    Admin,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Read => "read",
+            Operation::Write => "write",
+            Operation::Delete => "delete",
+            Operation::Admin => "admin",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// Rule of a policy, such as
+/// `{"identity": "gateway", "prefixes": ["session:"], "operations": ["read", "write"]}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Name of an API key or `sub` of a token, or `*` for every caller.
+    pub identity: String,
+    #[serde(default)]
+    pub effect: Effect,
+    /// Prefixes of the keys the rule covers; `""` covers every key.
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    pub operations: Vec<Operation>,
+}
+
+impl Rule {
+    fn applies(&self, identity: &str, operation: Operation, key: Option<&str>) -> bool {
+        (self.identity == "*" || self.identity == identity)
+            && self.operations.contains(&operation)
+            && key.is_none_or(|key| {
+                self.prefixes
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix.as_str()))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let policy: Policy = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+        for (index, rule) in policy.rules.iter().enumerate() {
+            if rule.identity.is_empty() {
+                return Err(format!("rule {} has no identity", index));
+            }
+            if rule.operations.is_empty() {
+                return Err(format!("rule {} has no operations", index));
+            }
+            // Such a rule would silently match no key; only `admin` needs none.
+            let on_keys = rule.operations.iter().any(|op| *op != Operation::Admin);
+            if on_keys && rule.prefixes.is_empty() {
+                return Err(format!("rule {} has no prefixes", index));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Checks whether `identity` may perform `operation` on `key`, or on no
+    /// key in particular for [`Operation::Admin`].
+    pub fn check(
+        &self,
+        identity: &str,
+        operation: Operation,
+        key: Option<&str>,
+    ) -> Result<(), Box<Denial>> {
+        let matching = |effect| {
+            self.rules
+                .iter()
+                .find(|rule| rule.effect == effect && rule.applies(identity, operation, key))
+        };
+        let denied = match matching(Effect::Deny) {
+            Some(rule) => Some(rule.clone()),
+            None if matching(Effect::Allow).is_some() => return Ok(()),
+            None => None,
+        };
+        Err(Box::new(Denial {
+            identity: identity.to_string(),
+            operation,
+            key: key.map(str::to_string),
+            rule: denied,
+        }))
+    }
+}
+
+/// Access a caller was refused, reported under `denied` in `403` bodies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Denial {
+    pub identity: String,
+    pub operation: Operation,
+    /// Key as stored, including its namespace prefix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Deny rule that matched; absent when no rule allows the access.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<Rule>,
+}
+
+impl fmt::Display for Denial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            Some(key) => write!(
+                f,
+                "{} may not {} key {:?}",
+                self.identity, self.operation, key
+            ),
+            None => write!(f, "{} may not use the admin endpoints", self.identity),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AclError {
+    #[error("failed to read {path}: {source}")]
+    File {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid policy in {path}: {reason}")]
+    Invalid { path: String, reason: String },
+}
+
+/// Policy read from a file, which [`Acl::reload`] reads again.
+pub struct Acl {
+    path: String,
+    policy: RwLock<Arc<Policy>>,
+}
+
+impl Acl {
+    pub fn load(path: &str) -> Result<Self, AclError> {
+        Ok(Self {
+            path: path.to_string(),
+            policy: RwLock::new(Arc::new(read(path)?)),
+        })
+    }
+
+    /// Replaces the policy with the current contents of its file, returning
+    /// the number of rules. An invalid file leaves the policy unchanged.
+    pub fn reload(&self) -> Result<usize, AclError> {
+        let policy = read(&self.path)?;
+        let rules = policy.rules.len();
+        *self.policy.write().expect("policy lock poisoned") = Arc::new(policy);
+        Ok(rules)
+    }
+
+    pub fn policy(&self) -> Arc<Policy> {
+        self.policy.read().expect("policy lock poisoned").clone()
+    }
+}
+
+fn read(path: &str) -> Result<Policy, AclError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| AclError::File {
+        path: path.to_string(),
+        source,
+    })?;
+    Policy::parse(&contents).map_err(|reason| AclError::Invalid {
+        path: path.to_string(),
+        reason,
+    })
+}
+
+/// Checks that `identity` may perform `operation`. Without a policy every
+/// caller may use the keys, and only admin identities the admin endpoints.
+pub fn authorize(
+    acl: Option<&Acl>,
+    identity: &Identity,
+    operation: Operation,
+    key: Option<&str>,
+) -> Result<(), ApiError> {
+    match acl {
+        Some(acl) => acl.policy().check(&identity.subject, operation, key),
+        None if operation == Operation::Admin && !identity.admin => Err(Box::new(Denial {
+            identity: identity.subject.clone(),
+            operation,
+            key: None,
+            rule: None,
+        })),
+        None => Ok(()),
+    }
+    .map_err(ApiError::Forbidden)
+}
+
+/// Access of the caller of a cache route to the keys of a namespace.
+#[derive(Clone)]
+pub struct Access {
+    /// `None` when requests are not authenticated.
+    identity: Option<Identity>,
+    acl: Option<Arc<Acl>>,
+}
+
+impl Access {
+    pub fn new(identity: Option<Identity>, acl: Option<Arc<Acl>>) -> Self {
+        Self { identity, acl }
+    }
+
+    /// Subject of the caller, `None` when requests are not authenticated.
+    pub fn subject(&self) -> Option<&str> {
+        self.identity
+            .as_ref()
+            .map(|identity| identity.subject.as_str())
+    }
+
+    /// Whether the caller may see work started by `owner`: its own, or
+    /// anyone's for callers allowed the admin endpoints.
+    pub fn may_view(&self, owner: Option<&str>) -> bool {
+        match &self.identity {
+            Some(identity) => {
+                owner == Some(identity.subject.as_str())
+                    || authorize(self.acl.as_deref(), identity, Operation::Admin, None).is_ok()
+            }
+            None => true,
+        }
+    }
+
+    pub fn check(&self, operation: Operation, ns: &Namespace, key: &str) -> Result<(), ApiError> {
+        match &self.identity {
+            Some(identity) => authorize(
+                self.acl.as_deref(),
+                identity,
+                operation,
+                Some(&ns.stored_key(key)),
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Access {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        Ok(Access::new(
+            parts.extensions.get::<Identity>().cloned(),
+            state.acl.clone(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"{
+        "rules": [
+            {"identity": "gateway", "prefixes": ["session:", "ns:billing:"], "operations": ["read", "write"]},
+            {"identity": "gateway", "effect": "deny", "prefixes": ["session:admin:"], "operations": ["write"]},
+            {"identity": "*", "prefixes": ["public:"], "operations": ["read"]},
+            {"identity": "ops", "operations": ["admin"]}
+        ]
+    }"#;
+
+    fn identity(subject: &str, admin: bool) -> Identity {
+        Identity {
+            subject: subject.into(),
+            admin,
+        }
+    }
+
+    #[test]
+    fn allows_listed_prefixes_and_operations() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let check = |identity, operation, key| policy.check(identity, operation, key).is_ok();
+        assert!(check("gateway", Operation::Read, Some("session:1")));
+        assert!(check(
+            "gateway",
+            Operation::Write,
+            Some("ns:billing:user:1")
+        ));
+        assert!(!check("gateway", Operation::Delete, Some("session:1")));
+        assert!(!check("gateway", Operation::Read, Some("user:1")));
+        assert!(check("anyone", Operation::Read, Some("public:logo")));
+        assert!(!check("anyone", Operation::Write, Some("public:logo")));
+        assert!(check("ops", Operation::Admin, None));
+        assert!(!check("gateway", Operation::Admin, None));
+    }
+
+    #[test]
+    fn reports_the_rule_denying_access() {
+        let policy = Policy::parse(POLICY).unwrap();
+        let denial = policy
+            .check("gateway", Operation::Write, Some("session:admin:1"))
+            .unwrap_err();
+        assert_eq!(denial.rule, Some(policy.rules[1].clone()));
+        assert_eq!(
+            denial.to_string(),
+            "gateway may not write key \"session:admin:1\""
+        );
+
+        let denial = policy
+            .check("gateway", Operation::Read, Some("user:1"))
+            .unwrap_err();
+        assert_eq!(denial.rule, None);
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        assert!(Policy::parse(r#"{"rules": [{"identity": "a", "operations": []}]}"#).is_err());
+        assert!(
+            Policy::parse(r#"{"rules": [{"identity": "a", "operations": ["list"]}]}"#).is_err()
+        );
+        assert!(Policy::parse(r#"{"rules": [], "extra": true}"#).is_err());
+        assert!(
+            Policy::parse(r#"{"rules": [{"identity": "a", "operations": ["read"]}]}"#).is_err()
+        );
+        assert!(Policy::parse(
+            r#"{"rules": [{"identity": "a", "prefixes": [], "operations": ["admin", "write"]}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn limits_admin_endpoints_without_a_policy() {
+        let admin = |identity: &Identity| authorize(None, identity, Operation::Admin, None);
+        assert!(admin(&identity("ops", true)).is_ok());
+        assert!(matches!(
+            admin(&identity("gateway", false)),
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(authorize(
+            None,
+            &identity("gateway", false),
+            Operation::Delete,
+            Some("a")
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn reloads_the_policy_file() {
+        let path = std::env::temp_dir().join(format!("cache-acl-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, POLICY).unwrap();
+        let acl = Acl::load(path).unwrap();
+        assert_eq!(acl.policy().rules.len(), 4);
+
+        std::fs::write(path, r#"{"rules": []}"#).unwrap();
+        assert_eq!(acl.reload().unwrap(), 0);
+        std::fs::write(path, "{").unwrap();
+        assert!(acl.reload().is_err());
+        std::fs::write(
+            path,
+            r#"{"rules": [{"identity": "a", "operations": ["read"]}]}"#,
+        )
+        .unwrap();
+        assert!(acl.reload().is_err());
+        assert!(acl.policy().rules.is_empty());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn callers_view_their_own_work() {
+        let acl = |identity| Access::new(Some(identity), None);
+        let gateway = acl(identity("gateway", false));
+        assert!(gateway.may_view(Some("gateway")));
+        assert!(!gateway.may_view(Some("ops")));
+        assert!(!gateway.may_view(None));
+        assert!(acl(identity("ops", true)).may_view(Some("gateway")));
+        assert!(Access::new(None, None).may_view(Some("gateway")));
+    }
+}
@@ -0,0 +1,429 @@
+//! Authentication of requests to the cache service.
+//!
+//! Callers present a static API key or a JWT as `Authorization: Bearer
+//! <credential>`, or an API key as `X-API-Key`. `/health` and `/metrics`
+//! stay open so orchestrators can probe the service and Prometheus can scrape
+//! it, and `/admin/*` is limited to admin identities, or to those the ACL
+//! policy grants `admin`.
+
+use crate::acl::{self, Operation};
+use crate::error::ApiError;
+use crate::AppState;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderName};
+use axum::middleware::Next;
+use axum::response::Response;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Scope of JWTs allowed to use the admin endpoints.
+pub const ADMIN_SCOPE: &str = "cache:admin";
+
+const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// Routes served without credentials.
+const OPEN_PATHS: [&str; 2] = ["/health", "/metrics"];
+
+/// Where credentials are loaded from; authentication is enabled as soon as
+/// one source is configured.
+#[derive(Debug, Clone, Default)]
+pub struct AuthSettings {
+    /// File of API keys, one `<identity> <key> [admin]` per line.
+    pub api_keys_file: Option<String>,
+    /// File holding the shared secret of HS256 tokens.
+    pub jwt_hs256_secret_file: Option<String>,
+    /// PEM file holding the public key of RS256 tokens.
+    pub jwt_rs256_public_key_file: Option<String>,
+    /// Required `iss` claim of tokens.
+    pub jwt_issuer: Option<String>,
+    /// Required `aud` claim of tokens.
+    pub jwt_audience: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("failed to read {path}: {source}")]
+    File {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("invalid credentials in {path}: {reason}")]
+    Invalid { path: String, reason: String },
+}
+
+/// Caller a request was authenticated as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Name of the API key, or `sub` of the token.
+    pub subject: String,
+    pub admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    scope: String,
+}
+
+/// Verifies the credentials of requests.
+#[derive(Default)]
+pub struct Authenticator {
+    /// Identities by SHA-1 of their API key, so that looking a key up does
+    /// not compare it byte by byte with the configured ones.
+    api_keys: HashMap<String, Identity>,
+    jwt_keys: HashMap<Algorithm, DecodingKey>,
+    issuer: Option<String>,
+    audience: Option<String>,
+}
+
+impl Authenticator {
+    /// Loads the configured credentials, or returns `None` when none are.
+    pub fn load(settings: &AuthSettings) -> Result<Option<Self>, AuthError> {
+        let mut auth = Authenticator {
+            issuer: settings.jwt_issuer.clone(),
+            audience: settings.jwt_audience.clone(),
+            ..Default::default()
+        };
+        let mut configured = false;
+        if let Some(path) = &settings.api_keys_file {
+            let contents = read(path)?;
+            let contents = String::from_utf8(contents).map_err(|e| invalid(path, e))?;
+            auth.add_api_keys(&contents).map_err(|e| invalid(path, e))?;
+            configured = true;
+        }
+        if let Some(path) = &settings.jwt_hs256_secret_file {
+            let secret = read(path)?;
+            let secret = secret.trim_ascii_end();
+            if secret.is_empty() {
+                return Err(invalid(path, "the secret is empty"));
+            }
+            auth.add_jwt_key(Algorithm::HS256, DecodingKey::from_secret(secret));
+            configured = true;
+        }
+        if let Some(path) = &settings.jwt_rs256_public_key_file {
+            let key = DecodingKey::from_rsa_pem(&read(path)?).map_err(|e| invalid(path, e))?;
+            auth.add_jwt_key(Algorithm::RS256, key);
+            configured = true;
+        }
+        Ok(configured.then_some(auth))
+    }
+
+    /// Adds API keys given as `<identity> <key> [admin]` lines; blank lines
+    /// and lines starting with `#` are skipped.
+    fn add_api_keys(&mut self, contents: &str) -> Result<(), String> {
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (subject, key, admin) = match fields[..] {
+                [subject, key] => (subject, key, false),
+                [subject, key, "admin"] => (subject, key, true),
+                _ => {
+                    return Err(format!(
+                        "line {}: expected `<identity> <key> [admin]`",
+                        number + 1
+                    ))
+                }
+            };
+            let identity = Identity {
+                subject: subject.to_string(),
+                admin,
+            };
+            if self.api_keys.insert(digest(key), identity).is_some() {
+                return Err(format!("line {}: duplicate key", number + 1));
+            }
+        }
+        Ok(())
+    }
+
+    fn add_jwt_key(&mut self, algorithm: Algorithm, key: DecodingKey) {
+        self.jwt_keys.insert(algorithm, key);
+    }
+
+    /// Resolves a bearer credential to the identity it belongs to.
+    pub fn authenticate(&self, credential: &str) -> Result<Identity, ApiError> {
+        if let Some(identity) = self.api_keys.get(&digest(credential)) {
+            return Ok(identity.clone());
+        }
+        if !self.jwt_keys.is_empty() && credential.split('.').count() == 3 {
+            return self.verify_token(credential);
+        }
+        Err(ApiError::Unauthorized("invalid credentials".into()))
+    }
+
+    fn verify_token(&self, token: &str) -> Result<Identity, ApiError> {
+        let invalid = |e: jsonwebtoken::errors::Error| {
+            ApiError::Unauthorized(format!("invalid token: {}", e))
+        };
+        let algorithm = jsonwebtoken::decode_header(token).map_err(invalid)?.alg;
+        let key = self.jwt_keys.get(&algorithm).ok_or_else(|| {
+            ApiError::Unauthorized(format!(
+                "tokens signed with {:?} are not accepted",
+                algorithm
+            ))
+        })?;
+        let mut validation = Validation::new(algorithm);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = jsonwebtoken::decode::<Claims>(token, key, &validation)
+            .map_err(invalid)?
+            .claims;
+        Ok(Identity {
+            admin: claims.scope.split_whitespace().any(|s| s == ADMIN_SCOPE),
+            subject: claims.sub,
+        })
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, AuthError> {
+    std::fs::read(path).map_err(|source| AuthError::File {
+        path: path.to_string(),
+        source,
+    })
+}
+
+fn invalid(path: &str, reason: impl ToString) -> AuthError {
+    AuthError::Invalid {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+fn digest(key: &str) -> String {
+    sha1_smol::Sha1::from(key).digest().to_string()
+}
+
+/// Credential of a request: the bearer token, else the `X-API-Key` header.
+fn credential(headers: &HeaderMap) -> Result<Option<&str>, ApiError> {
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        return value
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| Some(token.trim()))
+            .ok_or_else(|| ApiError::Unauthorized("expected a Bearer authorization".into()));
+    }
+    Ok(headers
+        .get(X_API_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim))
+}
+
+/// Middleware rejecting requests without valid credentials with `401` and
+/// unauthorized requests to `/admin/*` with `403`. The [`Identity`] of accepted
+/// requests is added to their extensions.
+pub async fn authenticate(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let Some(auth) = &state.auth else {
+        return Ok(next.run(request).await);
+    };
+    let path = request.uri().path();
+    if OPEN_PATHS.contains(&path) {
+        return Ok(next.run(request).await);
+    }
+    let admin_only = path.starts_with("/admin/");
+    let credential = credential(request.headers())?
+        .ok_or_else(|| ApiError::Unauthorized("missing credentials".into()))?;
+    let identity = auth.authenticate(credential)?;
+    if admin_only {
+        acl::authorize(state.acl.as_deref(), &identity, Operation::Admin, None)?;
+    }
+    tracing::debug!(subject = %identity.subject, "authenticated");
+    request.extensions_mut().insert(identity);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn authenticator() -> Authenticator {
+        let mut auth = Authenticator {
+            issuer: Some("issuer".into()),
+            ..Default::default()
+        };
+        auth.add_api_keys("# comment\n\ngateway k-gateway\nops k-ops admin\n")
+            .unwrap();
+        auth.add_jwt_key(Algorithm::HS256, DecodingKey::from_secret(SECRET));
+        auth
+    }
+
+    fn token(claims: serde_json::Value, secret: &[u8]) -> String {
+        let header = Header::new(Algorithm::HS256);
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn authenticates_api_keys() {
+        let auth = authenticator();
+        let gateway = auth.authenticate("k-gateway").unwrap();
+        assert_eq!(gateway.subject, "gateway");
+        assert!(!gateway.admin);
+        assert!(auth.authenticate("k-ops").unwrap().admin);
+        assert!(matches!(
+            auth.authenticate("k-other"),
+            Err(ApiError::Unauthorized(_))
+        ));
+
+        let mut auth = Authenticator::default();
+        assert!(auth.add_api_keys("a key\nb key\n").is_err());
+        assert!(auth.add_api_keys("a key root\n").is_err());
+    }
+
+    #[test]
+    fn verifies_hs256_tokens() {
+        let auth = authenticator();
+        let claims = |iss: &str, exp: u64| {
+            let scope = ADMIN_SCOPE;
+            serde_json::json!({ "sub": "billing", "iss": iss, "exp": exp, "scope": scope })
+        };
+
+        let identity = auth
+            .authenticate(&token(claims("issuer", now() + 60), SECRET))
+            .unwrap();
+        assert_eq!(
+            identity,
+            Identity {
+                subject: "billing".into(),
+                admin: true
+            }
+        );
+
+        let rejected = [
+            token(claims("issuer", now() - 3600), SECRET),
+            token(claims("other", now() + 60), SECRET),
+            token(claims("issuer", now() + 60), b"wrong-secret"),
+            token(
+                serde_json::json!({ "iss": "issuer", "exp": now() + 60 }),
+                SECRET,
+            ),
+        ];
+        for token in rejected {
+            assert!(matches!(
+                auth.authenticate(&token),
+                Err(ApiError::Unauthorized(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn reads_credentials_from_headers() {
+        let headers = |name: HeaderName, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(credential(&HeaderMap::new()).unwrap(), None);
+        assert_eq!(
+            credential(&headers(header::AUTHORIZATION, "Bearer k-ops")).unwrap(),
+            Some("k-ops")
+        );
+        assert_eq!(
+            credential(&headers(X_API_KEY, "k-ops")).unwrap(),
+            Some("k-ops")
+        );
+        assert!(credential(&headers(header::AUTHORIZATION, "Basic b3Bz")).is_err());
+    }
+
+    fn app() -> axum::Router {
+        use crate::backend::{MemoryBackend, MemoryLimits};
+        use std::sync::Arc;
+
+        let backend = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let mut state = AppState::new(backend);
+        state.auth = Some(Arc::new(authenticator()));
+        crate::app(state)
+    }
+
+    async fn status(app: &axum::Router, request: Request) -> axum::http::StatusCode {
+        use tower::ServiceExt;
+
+        app.clone().oneshot(request).await.unwrap().status()
+    }
+
+    fn get(path: &str, credential: Option<(HeaderName, String)>) -> Request {
+        let mut request = Request::get(path);
+        if let Some((name, value)) = credential {
+            request = request.header(name, value);
+        }
+        request.body(axum::body::Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn leaves_health_and_metrics_open() {
+        use axum::http::StatusCode;
+
+        let app = app();
+        assert_eq!(status(&app, get("/health", None)).await, StatusCode::OK);
+        assert_eq!(status(&app, get("/metrics", None)).await, StatusCode::OK);
+        assert_eq!(
+            status(&app, get("/cache/a", None)).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(&app, get("/metrics/other", None)).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn admits_valid_credentials() {
+        use axum::http::StatusCode;
+
+        let app = app();
+        let api_key = |key: &str| Some((X_API_KEY, key.to_string()));
+        let bearer = |token: &str| Some((header::AUTHORIZATION, format!("Bearer {}", token)));
+        let jwt = token(
+            serde_json::json!({ "sub": "billing", "iss": "issuer", "exp": now() + 60 }),
+            SECRET,
+        );
+
+        // Misses, so the request got past authentication.
+        let cases = [
+            (get("/cache/a", api_key("k-gateway")), StatusCode::NOT_FOUND),
+            (get("/cache/a", bearer("k-gateway")), StatusCode::NOT_FOUND),
+            (get("/cache/a", bearer(&jwt)), StatusCode::NOT_FOUND),
+            (
+                get("/cache/a", api_key("k-other")),
+                StatusCode::UNAUTHORIZED,
+            ),
+            (
+                get("/admin/stats", api_key("k-gateway")),
+                StatusCode::FORBIDDEN,
+            ),
+            (get("/admin/stats", bearer(&jwt)), StatusCode::FORBIDDEN),
+            (get("/admin/stats", api_key("k-ops")), StatusCode::OK),
+        ];
+        for (request, expected) in cases {
+            let uri = request.uri().clone();
+            assert_eq!(status(&app, request).await, expected, "{}", uri);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use super::{
+    BackendError, BackendResult, CacheBackend, Counter, Hit, KeyEvents, KeyInfo, KeyTtl, ScanPage,
+    SetCondition, SetItem,
+};
+use crate::circuit_breaker::CircuitBreaker;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Backend failing fast while its circuit breaker is open.
+///
+/// Only errors meaning the storage is unreachable count as failures;
+/// rejected requests (e.g. oversized entries) leave the breaker alone.
+pub struct BreakerBackend {
+    inner: Arc<dyn CacheBackend>,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl BreakerBackend {
+    pub fn new(inner: Arc<dyn CacheBackend>, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { inner, breaker }
+    }
+
+    async fn guard<T>(&self, op: impl Future<Output = BackendResult<T>>) -> BackendResult<T> {
+        self.guard_by(op, |result| matches!(result, Err(e) if e.is_unavailable()))
+            .await
+    }
+
+    /// [`BreakerBackend::guard`] with `failed` telling which results count
+    /// as failures.
+    async fn guard_by<T>(
+        &self,
+        op: impl Future<Output = BackendResult<T>>,
+        failed: impl FnOnce(&BackendResult<T>) -> bool,
+    ) -> BackendResult<T> {
+        let permit =
+            self.breaker
+                .try_acquire()
+                .map_err(|retry_after| BackendError::CircuitOpen {
+                    breaker: self.breaker.name().to_string(),
+                    retry_after,
+                })?;
+        let result = op.await;
+        permit.record(!failed(&result));
+        result
+    }
+}
+
+#[async_trait]
+impl CacheBackend for BreakerBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        self.guard(self.inner.get(key)).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        self.guard(self.inner.set(key, value, ttl)).await
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        self.guard(self.inner.set_if(key, value, ttl, condition))
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        self.guard(self.inner.delete(key)).await
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        self.guard(self.inner.ttl(key)).await
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        self.guard(self.inner.set_ttl(key, ttl)).await
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        self.guard(self.inner.increment(key, by, ttl)).await
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        self.guard(self.inner.key_count()).await
+    }
+
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.guard(self.inner.scan(cursor, pattern, count)).await
+    }
+
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        self.guard(self.inner.tag(key, tags)).await
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.guard(self.inner.tagged(tag, cursor, count)).await
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        self.guard(self.inner.untag(tag, keys)).await
+    }
+
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        self.guard(self.inner.watch()).await
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        self.guard(self.inner.get_with_ttl(key)).await
+    }
+
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        self.guard(self.inner.lookup(key)).await
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        self.guard(self.inner.get_many_with_ttl(keys)).await
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        self.guard(self.inner.inspect_many(keys)).await
+    }
+
+    /// Batches report most failures per item, and fail the breaker when any
+    /// item could not reach the storage.
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        self.guard_by(self.inner.set_many(items), |result| match result {
+            Ok(results) => results
+                .iter()
+                .any(|result| matches!(result, Err(e) if e.is_unavailable())),
+            Err(e) => e.is_unavailable(),
+        })
+        .await
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        let mut health = self.inner.health().await;
+        if health.is_null() {
+            health = serde_json::json!({});
+        }
+        health["circuit_breaker"] = self.breaker.snapshot();
+        health
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, MemoryLimits, ShardedBackend};
+    use crate::circuit_breaker::BreakerSettings;
+
+    fn breaker(name: &str) -> Arc<CircuitBreaker> {
+        Arc::new(CircuitBreaker::new(
+            name,
+            BreakerSettings {
+                failure_threshold: 1,
+                reset_timeout: Duration::from_secs(60),
+                half_open_max_requests: 1,
+            },
+        ))
+    }
+
+    #[tokio::test]
+    async fn unreachable_batch_items_trip_the_breaker() {
+        let memory = || {
+            Arc::new(MemoryBackend::new(MemoryLimits {
+                max_entries: 100,
+                max_bytes: 4096,
+            })) as Arc<dyn CacheBackend>
+        };
+        let tripped = breaker("down");
+        tripped.try_acquire().unwrap().record(false);
+        let down: Arc<dyn CacheBackend> = Arc::new(BreakerBackend::new(memory(), tripped));
+        let shards = ShardedBackend::new(vec![("up".into(), memory()), ("down".into(), down)], 160);
+        let cache = BreakerBackend::new(Arc::new(shards), breaker("outer"));
+
+        let keys: Vec<String> = (0..20).map(|i| format!("key:{}", i)).collect();
+        let items: Vec<SetItem> = keys
+            .iter()
+            .map(|key| SetItem {
+                key,
+                value: b"1",
+                ttl: None,
+            })
+            .collect();
+        let results = cache.set_many(&items).await.unwrap();
+        assert!(results.iter().any(Result::is_ok));
+        assert!(results.iter().any(Result::is_err));
+        assert!(matches!(
+            cache.get("key:0").await,
+            Err(BackendError::CircuitOpen { .. })
+        ));
+    }
+}
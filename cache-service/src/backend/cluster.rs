@@ -0,0 +1,286 @@
+use super::{Connect, ConnectionSettings, RedisBackend, ScanCursor};
+use async_trait::async_trait;
+use redis::cluster::ClusterClient;
+use redis::cluster_async::ClusterConnection;
+use redis::cluster_routing::{get_slot, Route, RoutingInfo, SingleNodeRoutingInfo, SlotAddr};
+use redis::{
+    from_redis_value, Client, ConnectionAddr, ConnectionInfo, ErrorKind, IntoConnectionInfo,
+    RedisResult, Value,
+};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Backend storing entries in a Redis Cluster.
+///
+/// Commands are routed by key slot and follow `MOVED`/`ASK` redirects,
+/// refreshing the slot map as the cluster reshards.
+pub type ClusterBackend = RedisBackend<ClusterConnector>;
+
+impl ClusterBackend {
+    /// Creates a backend discovering the cluster from `seed_nodes`.
+    pub fn cluster(seed_nodes: &[String], settings: ConnectionSettings) -> RedisResult<Self> {
+        let client = ClusterClient::builder(seed_nodes.to_vec())
+            .retries(settings.reconnect_retries as u32)
+            .min_retry_wait(settings.reconnect_backoff.as_millis() as u64)
+            .build()?;
+        let seed = seed_nodes
+            .first()
+            .ok_or((ErrorKind::InvalidClientConfig, "no cluster seed nodes"))?
+            .as_str()
+            .into_connection_info()?;
+        Ok(Self::new(ClusterConnector { client, seed }, settings))
+    }
+}
+
+/// Connects to a Redis Cluster.
+///
+/// Cluster connections cannot subscribe to notifications, so nodes are
+/// also reached directly, with the credentials and TLS of the first seed.
+pub struct ClusterConnector {
+    client: ClusterClient,
+    seed: ConnectionInfo,
+}
+
+impl ClusterConnector {
+    fn node(&self, host: String, port: u16) -> RedisResult<Client> {
+        let addr = match &self.seed.addr {
+            ConnectionAddr::TcpTls {
+                insecure,
+                tls_params,
+                ..
+            } => ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure: *insecure,
+                tls_params: tls_params.clone(),
+            },
+            _ => ConnectionAddr::Tcp(host, port),
+        };
+        Client::open(ConnectionInfo {
+            addr,
+            redis: self.seed.redis.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Connect for ClusterConnector {
+    type Connection = ClusterConnection;
+
+    fn name(&self) -> String {
+        "cluster".into()
+    }
+
+    async fn connect(&self, _settings: &ConnectionSettings) -> RedisResult<ClusterConnection> {
+        self.client.get_async_connection().await
+    }
+
+    /// Pipelines may not cross slots, so batches are split by slot.
+    fn pipeline_groups(&self, keys: &[&str]) -> Vec<Vec<usize>> {
+        let mut slots: BTreeMap<u16, Vec<usize>> = BTreeMap::new();
+        for (index, key) in keys.iter().enumerate() {
+            slots
+                .entry(get_slot(key.as_bytes()))
+                .or_default()
+                .push(index);
+        }
+        slots.into_values().collect()
+    }
+
+    /// `SCAN` only walks the node it is sent to, so the primaries are
+    /// scanned one after the other, each identified by the first slot it
+    /// serves.
+    async fn scan(
+        &self,
+        mut conn: ClusterConnection,
+        cursor: ScanCursor,
+        pattern: &str,
+        count: usize,
+    ) -> RedisResult<(Vec<String>, Option<ScanCursor>)> {
+        let slots: Vec<Vec<Value>> = redis::cmd("CLUSTER")
+            .arg("SLOTS")
+            .query_async(&mut conn)
+            .await?;
+        let nodes = primary_slots(&slots)?;
+        let Some(node) = cursor.node.or(nodes.first().copied()) else {
+            return Ok((Vec::new(), None));
+        };
+        let route = Route::new(node, SlotAddr::Master);
+        let reply = conn
+            .route_command(
+                redis::cmd("SCAN")
+                    .arg(cursor.position)
+                    .arg("MATCH")
+                    .arg(pattern)
+                    .arg("COUNT")
+                    .arg(count),
+                RoutingInfo::SingleNode(SingleNodeRoutingInfo::SpecificNode(route)),
+            )
+            .await?;
+        let (position, keys): (u64, Vec<String>) = from_redis_value(&reply)?;
+        let next = if position != 0 {
+            Some(ScanCursor {
+                node: Some(node),
+                position,
+            })
+        } else {
+            nodes
+                .into_iter()
+                .find(|&next| next > node)
+                .map(|next| ScanCursor {
+                    node: Some(next),
+                    position: 0,
+                })
+        };
+        Ok((keys, next))
+    }
+
+    /// Every primary, as keys are written and expired there.
+    async fn event_sources(&self, mut conn: ClusterConnection) -> RedisResult<Vec<Client>> {
+        let slots: Vec<Vec<Value>> = redis::cmd("CLUSTER")
+            .arg("SLOTS")
+            .query_async(&mut conn)
+            .await?;
+        primaries(&slots)?
+            .into_keys()
+            .map(|(host, port)| self.node(host, port))
+            .collect()
+    }
+
+    async fn health(&self, mut conn: ClusterConnection) -> RedisResult<serde_json::Value> {
+        let nodes: String = redis::cmd("CLUSTER")
+            .arg("NODES")
+            .query_async(&mut conn)
+            .await?;
+        let nodes: Vec<ClusterNode> = nodes.lines().filter_map(ClusterNode::parse).collect();
+        let healthy = nodes.iter().filter(|node| node.healthy).count();
+        Ok(json!({
+            "nodes": nodes,
+            "healthy_nodes": healthy,
+            "total_nodes": nodes.len(),
+        }))
+    }
+}
+
+/// First slot served by each primary in a `CLUSTER SLOTS` reply, in order.
+fn primary_slots(ranges: &[Vec<Value>]) -> RedisResult<Vec<u16>> {
+    let mut slots: Vec<u16> = primaries(ranges)?.into_values().collect();
+    slots.sort_unstable();
+    Ok(slots)
+}
+
+/// Address of each primary in a `CLUSTER SLOTS` reply, with the first slot
+/// it serves.
+fn primaries(ranges: &[Vec<Value>]) -> RedisResult<BTreeMap<(String, u16), u16>> {
+    let mut primaries: BTreeMap<(String, u16), u16> = BTreeMap::new();
+    for range in ranges {
+        let (Some(start), Some(primary)) = (range.first(), range.get(2)) else {
+            continue;
+        };
+        let start: u16 = from_redis_value(start)?;
+        let (host, port): (String, u16) = match from_redis_value::<Vec<Value>>(primary)?.as_slice()
+        {
+            [host, port, ..] => (from_redis_value(host)?, from_redis_value(port)?),
+            _ => continue,
+        };
+        let first = primaries.entry((host, port)).or_insert(start);
+        *first = (*first).min(start);
+    }
+    Ok(primaries)
+}
+
+/// One line of `CLUSTER NODES` output.
+#[derive(Debug, Serialize)]
+struct ClusterNode {
+    id: String,
+    address: String,
+    role: &'static str,
+    flags: Vec<String>,
+    link_state: String,
+    slots: Vec<String>,
+    healthy: bool,
+}
+
+impl ClusterNode {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 {
+            return None;
+        }
+        let flags: Vec<String> = fields[2].split(',').map(str::to_string).collect();
+        let role = if flags.iter().any(|flag| flag == "master") {
+            "primary"
+        } else {
+            "replica"
+        };
+        let failing = flags.iter().any(|flag| flag == "fail" || flag == "fail?");
+        let link_state = fields[7].to_string();
+        Some(ClusterNode {
+            id: fields[0].to_string(),
+            // Strip the cluster bus port and optional hostname.
+            address: fields[1]
+                .split(['@', ','])
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            role,
+            healthy: !failing && link_state == "connected",
+            flags,
+            link_state,
+            slots: fields[8..].iter().map(|slot| slot.to_string()).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cluster_nodes_lines() {
+        let primary = ClusterNode::parse(
+            "07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,node-4 myself,master - 0 1426238317239 4 connected 0-5460",
+        )
+        .unwrap();
+        assert_eq!(primary.address, "127.0.0.1:30004");
+        assert_eq!(primary.role, "primary");
+        assert_eq!(primary.slots, vec!["0-5460"]);
+        assert!(primary.healthy);
+
+        let replica = ClusterNode::parse(
+            "e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001 slave,fail 07c37dfeb235213a872192d90877d0cd55635b91 0 1426238316232 1 disconnected",
+        )
+        .unwrap();
+        assert_eq!(replica.role, "replica");
+        assert!(!replica.healthy);
+        assert!(ClusterNode::parse("").is_none());
+    }
+
+    #[test]
+    fn orders_primaries_by_their_first_slot() {
+        let node =
+            |port: i64| Value::Bulk(vec![Value::Data(b"127.0.0.1".to_vec()), Value::Int(port)]);
+        let range = |start: i64, end: i64, port: i64| {
+            vec![
+                Value::Int(start),
+                Value::Int(end),
+                node(port),
+                node(port + 3),
+            ]
+        };
+        let slots = [
+            range(10923, 16383, 30003),
+            range(0, 5460, 30001),
+            range(5461, 10922, 30002),
+            range(5000, 5460, 30003),
+        ];
+        assert_eq!(primary_slots(&slots).unwrap(), vec![0, 5000, 5461]);
+
+        let cursor = ScanCursor::parse("5461:17").unwrap();
+        assert_eq!(cursor.node, Some(5461));
+        assert_eq!(cursor.to_string(), "5461:17");
+        assert_eq!(ScanCursor::parse("42").unwrap().node, None);
+        assert!(ScanCursor::parse("a:1").is_none());
+    }
+}
@@ -0,0 +1,381 @@
+use super::{BackendError, BackendResult};
+use crate::metrics;
+use async_trait::async_trait;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::{Client, RedisResult};
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{OnceCell, Semaphore};
+use tracing::field::Empty;
+use tracing::Instrument;
+
+/// Tuning of the shared Redis connection.
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionSettings {
+    /// Maximum number of commands in flight at once; further callers wait.
+    pub max_in_flight: usize,
+    /// Time allowed for a command, including waiting for a slot and connecting.
+    pub command_timeout: Duration,
+    /// Base delay of the exponential reconnect backoff.
+    pub reconnect_backoff: Duration,
+    /// Reconnect attempts made before a command fails.
+    pub reconnect_retries: usize,
+    /// Whether watching keys may turn on the keyspace notifications it needs
+    /// with `CONFIG SET`, rather than only warn when they are off.
+    pub configure_notifications: bool,
+}
+
+/// Position of a `SCAN` iteration over a Redis deployment, formatted as
+/// `<position>`, or `<node>:<position>` on a cluster.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanCursor {
+    /// First slot served by the cluster primary being scanned.
+    pub node: Option<u16>,
+    /// Cursor returned by `SCAN` on that node.
+    pub position: u64,
+}
+
+impl ScanCursor {
+    pub fn parse(cursor: &str) -> Option<Self> {
+        match cursor.split_once(':') {
+            Some((node, position)) => Some(Self {
+                node: Some(node.parse().ok()?),
+                position: position.parse().ok()?,
+            }),
+            None => Some(Self {
+                node: None,
+                position: cursor.parse().ok()?,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for ScanCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.node {
+            Some(node) => write!(f, "{}:{}", node, self.position),
+            None => write!(f, "{}", self.position),
+        }
+    }
+}
+
+/// Opens the shared connection for a Redis deployment.
+#[async_trait]
+pub trait Connect: Send + Sync + 'static {
+    type Connection: ConnectionLike + Clone + Send + Sync;
+
+    /// Name identifying the deployment in logs and metrics.
+    fn name(&self) -> String;
+
+    async fn connect(&self, settings: &ConnectionSettings) -> RedisResult<Self::Connection>;
+
+    /// Nodes whose keyspace notifications together cover every key, to be
+    /// subscribed to separately.
+    async fn event_sources(&self, conn: Self::Connection) -> RedisResult<Vec<Client>>;
+
+    /// Deployment-specific health details, reported by `/health`. Must reach
+    /// Redis, since its outcome decides the connection state reported.
+    async fn health(&self, mut conn: Self::Connection) -> RedisResult<serde_json::Value> {
+        redis::cmd("PING").query_async::<_, ()>(&mut conn).await?;
+        Ok(serde_json::Value::Null)
+    }
+
+    /// Splits a batch of keys into groups that can share one pipeline,
+    /// as indices into `keys`.
+    fn pipeline_groups(&self, keys: &[&str]) -> Vec<Vec<usize>> {
+        vec![(0..keys.len()).collect()]
+    }
+
+    /// Runs one `SCAN` step, returning the keys found and where to resume,
+    /// `None` once every key was visited.
+    async fn scan(
+        &self,
+        mut conn: Self::Connection,
+        cursor: ScanCursor,
+        pattern: &str,
+        count: usize,
+    ) -> RedisResult<(Vec<String>, Option<ScanCursor>)> {
+        let (position, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor.position)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(count)
+            .query_async(&mut conn)
+            .await?;
+        let next = ScanCursor {
+            node: None,
+            position,
+        };
+        Ok((keys, (position != 0).then_some(next)))
+    }
+}
+
+#[async_trait]
+impl Connect for Client {
+    type Connection = ConnectionManager;
+
+    fn name(&self) -> String {
+        self.get_connection_info().addr.to_string()
+    }
+
+    async fn connect(&self, settings: &ConnectionSettings) -> RedisResult<ConnectionManager> {
+        ConnectionManager::new_with_backoff(
+            self.clone(),
+            2,
+            settings.reconnect_backoff.as_millis() as u64,
+            settings.reconnect_retries,
+        )
+        .await
+    }
+
+    async fn event_sources(&self, _conn: ConnectionManager) -> RedisResult<Vec<Client>> {
+        Ok(vec![self.clone()])
+    }
+}
+
+/// Health of the shared connection as last observed by a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// No connection has been established yet.
+    Connecting,
+    Connected,
+    /// The last command hit a connection error; the next one reconnects.
+    Reconnecting,
+    /// The last command timed out, so Redis may be unreachable.
+    Disconnected,
+}
+
+impl ConnectionState {
+    const ALL: [ConnectionState; 4] = [
+        ConnectionState::Connecting,
+        ConnectionState::Connected,
+        ConnectionState::Reconnecting,
+        ConnectionState::Disconnected,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Disconnected => "disconnected",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ConnectionState::Connected,
+            2 => ConnectionState::Reconnecting,
+            3 => ConnectionState::Disconnected,
+            _ => ConnectionState::Connecting,
+        }
+    }
+}
+
+/// A single multiplexed connection shared by every request.
+///
+/// The connection is opened on first use and re-established by the
+/// underlying client, with exponential backoff, after I/O errors. Concurrent commands are bounded
+/// by a semaphore so a slow Redis applies backpressure instead of piling
+/// up unbounded work.
+pub struct RedisConnection<C: Connect = Client> {
+    connector: C,
+    name: String,
+    settings: ConnectionSettings,
+    conn: OnceCell<C::Connection>,
+    permits: Semaphore,
+    state: AtomicU8,
+    last_error: Mutex<Option<String>>,
+}
+
+impl<C: Connect> RedisConnection<C> {
+    pub fn new(connector: C, settings: ConnectionSettings) -> Self {
+        let name = connector.name();
+        record_state(&name, ConnectionState::Connecting);
+        Self {
+            connector,
+            name,
+            settings,
+            conn: OnceCell::new(),
+            permits: Semaphore::new(settings.max_in_flight),
+            state: AtomicU8::new(ConnectionState::Connecting as u8),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        ConnectionState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    pub fn connector(&self) -> &C {
+        &self.connector
+    }
+
+    pub fn settings(&self) -> &ConnectionSettings {
+        &self.settings
+    }
+
+    async fn connection(&self) -> RedisResult<C::Connection> {
+        let conn = self
+            .conn
+            .get_or_try_init(|| self.connector.connect(&self.settings))
+            .await?;
+        Ok(conn.clone())
+    }
+
+    /// Runs `command` on the shared connection within the in-flight and
+    /// timeout bounds, recording the resulting connection state.
+    ///
+    /// Each call is traced as a client span named after `operation`.
+    pub async fn run<T, F, Fut>(&self, operation: &'static str, command: F) -> BackendResult<T>
+    where
+        F: FnOnce(C::Connection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let span = tracing::info_span!(
+            "redis_command",
+            otel.name = operation,
+            otel.kind = "client",
+            otel.status_code = Empty,
+            db.system = "redis",
+            db.operation.name = operation,
+            server.address = %self.name,
+            error.type = Empty,
+        );
+        let result = self.execute(command).instrument(span.clone()).await;
+        if let Err(e) = &result {
+            span.record("otel.status_code", "ERROR");
+            span.record("error.type", e.kind());
+        }
+        result
+    }
+
+    async fn execute<T, F, Fut>(&self, command: F) -> BackendResult<T>
+    where
+        F: FnOnce(C::Connection) -> Fut,
+        Fut: Future<Output = RedisResult<T>>,
+    {
+        let attempt = async {
+            let _permit = self
+                .permits
+                .acquire()
+                .await
+                .expect("semaphore is never closed");
+            command(self.connection().await?).await
+        };
+        let result = match tokio::time::timeout(self.settings.command_timeout, attempt).await {
+            Ok(result) => result,
+            Err(_) => {
+                if self.state() != ConnectionState::Disconnected {
+                    tracing::warn!("Redis command to {} timed out", self.name);
+                }
+                self.set_state(ConnectionState::Disconnected);
+                *self.last_error.lock().unwrap() = Some("command timed out".into());
+                return Err(BackendError::Timeout(self.settings.command_timeout));
+            }
+        };
+
+        match &result {
+            Ok(_) => self.set_state(ConnectionState::Connected),
+            Err(e) if e.is_io_error() || e.is_connection_dropped() || e.is_connection_refusal() => {
+                if self.state() != ConnectionState::Reconnecting {
+                    tracing::warn!("Redis connection to {} lost: {}", self.name, e);
+                }
+                self.set_state(ConnectionState::Reconnecting);
+                *self.last_error.lock().unwrap() = Some(e.to_string());
+            }
+            Err(_) => {}
+        }
+        Ok(result?)
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        let previous = self.state.swap(state as u8, Ordering::Relaxed);
+        if previous == state as u8 {
+            return;
+        }
+        record_state(&self.name, state);
+        if state == ConnectionState::Connected {
+            tracing::info!("Redis connection to {} established", self.name);
+        }
+    }
+
+    /// Connection state together with the connector's health details.
+    pub async fn health(&self) -> serde_json::Value {
+        let details = self.run("health", |conn| self.connector.health(conn)).await;
+        let mut health = json!({ "state": self.state() });
+        match details {
+            Ok(serde_json::Value::Null) => {}
+            Ok(details) => health["details"] = details,
+            Err(e) => health["error"] = e.to_string().into(),
+        }
+        health
+    }
+
+    pub fn stats(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "state": self.state(),
+            "in_flight": self.settings.max_in_flight - self.permits.available_permits(),
+            "max_in_flight": self.settings.max_in_flight,
+            "last_error": *self.last_error.lock().unwrap(),
+        })
+    }
+}
+
+fn record_state(name: &str, state: ConnectionState) {
+    for candidate in ConnectionState::ALL {
+        metrics::REDIS_CONNECTION_STATE
+            .with_label_values(&[name, candidate.as_str()])
+            .set(i64::from(candidate == state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deployment that never answers.
+    struct Stalled;
+
+    #[async_trait]
+    impl Connect for Stalled {
+        type Connection = ConnectionManager;
+
+        fn name(&self) -> String {
+            "stalled".into()
+        }
+
+        async fn connect(&self, _settings: &ConnectionSettings) -> RedisResult<ConnectionManager> {
+            std::future::pending().await
+        }
+
+        async fn event_sources(&self, _conn: ConnectionManager) -> RedisResult<Vec<Client>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn timeouts_report_the_connection_down() {
+        let conn = RedisConnection::new(
+            Stalled,
+            ConnectionSettings {
+                max_in_flight: 1,
+                command_timeout: Duration::from_millis(10),
+                reconnect_backoff: Duration::from_millis(1),
+                reconnect_retries: 0,
+                configure_notifications: false,
+            },
+        );
+        let health = conn.health().await;
+        assert_eq!(health["state"], "disconnected");
+        assert_eq!(conn.stats()["last_error"], "command timed out");
+    }
+}
@@ -0,0 +1,116 @@
+//! Glob patterns as understood by Redis `SCAN ... MATCH`, so that every
+//! backend lists the same keys for a pattern.
+
+/// Whether `key` matches `pattern`: `*` matches any run of characters, `?`
+/// any single one, `[abc]`, `[a-z]` and `[^a]` a set of them, and `\`
+/// escapes the next character.
+pub fn glob_match(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+    matches(&pattern, &key)
+}
+
+fn matches(pattern: &[char], key: &[char]) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // Position after the last `*` and the key position it is retried from.
+    let mut backtrack = None;
+    while k < key.len() {
+        let step = match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, k));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => class(pattern, p + 1, key[k]),
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == key[k]).then_some(p + 2),
+            Some(&c) => (c == key[k]).then_some(p + 1),
+            None => None,
+        };
+        match (step, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                k += 1;
+            }
+            (None, Some((star, from))) => {
+                p = star;
+                k = from + 1;
+                backtrack = Some((star, from + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the class opened just before `start`, returning the
+/// position after its closing `]`.
+fn class(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start;
+    let negated = pattern.get(i) == Some(&'^');
+    if negated {
+        i += 1;
+    }
+    let mut matched = false;
+    while i < pattern.len() && pattern[i] != ']' {
+        if pattern[i] == '\\' && i + 1 < pattern.len() {
+            matched |= pattern[i + 1] == c;
+            i += 2;
+        } else if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            let (low, high) = (
+                pattern[i].min(pattern[i + 2]),
+                pattern[i].max(pattern[i + 2]),
+            );
+            matched |= (low..=high).contains(&c);
+            i += 3;
+        } else {
+            matched |= pattern[i] == c;
+            i += 1;
+        }
+    }
+    // Like Redis, an unterminated class extends to the end of the pattern.
+    (matched != negated).then_some((i + 1).min(pattern.len()))
+}
+
+/// Escapes `literal` so that it matches only itself, such as a key prefix
+/// placed in front of a pattern.
+pub fn escape_glob(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_like_redis() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("user:*", "user:1"));
+        assert!(!glob_match("user:*", "session:1"));
+        assert!(glob_match("*:1", "user:1"));
+        assert!(glob_match("u*r:*1", "user:21"));
+        assert!(glob_match("user:?", "user:1"));
+        assert!(!glob_match("user:?", "user:12"));
+        assert!(glob_match("user:[0-9]", "user:7"));
+        assert!(glob_match("user:[^0-9]", "user:x"));
+        assert!(!glob_match("user:[^0-9]", "user:7"));
+        assert!(glob_match("h[ae]llo", "hallo"));
+        assert!(!glob_match("h[ae]llo", "hillo"));
+        assert!(glob_match("a\\*b", "a*b"));
+        assert!(!glob_match("a\\*b", "axb"));
+    }
+
+    #[test]
+    fn escaped_literals_match_only_themselves() {
+        let prefix = escape_glob("ns:a*[b]?\\");
+        assert!(glob_match(&format!("{}*", prefix), "ns:a*[b]?\\key"));
+        assert!(!glob_match(&format!("{}*", prefix), "ns:ax[b]?\\key"));
+    }
+}
@@ -0,0 +1,188 @@
+use super::{
+    BackendResult, CacheBackend, Counter, Hit, KeyEvents, KeyInfo, KeyTtl, ScanPage, SetCondition,
+    SetItem,
+};
+use crate::metrics;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Backend recording Prometheus metrics for every operation.
+pub struct InstrumentedBackend {
+    inner: Arc<dyn CacheBackend>,
+}
+
+impl InstrumentedBackend {
+    pub fn new(inner: Arc<dyn CacheBackend>) -> Self {
+        Self { inner }
+    }
+
+    async fn observe<T>(
+        &self,
+        operation: &str,
+        op: impl Future<Output = BackendResult<T>>,
+    ) -> BackendResult<T> {
+        let start = Instant::now();
+        let result = op.await;
+        let outcome = if result.is_ok() { "ok" } else { "error" };
+        metrics::BACKEND_OPERATION_DURATION
+            .with_label_values(&[operation, outcome])
+            .observe(start.elapsed().as_secs_f64());
+        if let Err(e) = &result {
+            metrics::BACKEND_ERRORS_TOTAL
+                .with_label_values(&[operation, e.kind()])
+                .inc();
+        }
+        result
+    }
+
+    fn record_lookup(value: Option<&[u8]>) {
+        let result = if value.is_some() { "hit" } else { "miss" };
+        metrics::CACHE_LOOKUPS_TOTAL
+            .with_label_values(&[result])
+            .inc();
+        if let Some(value) = value {
+            metrics::VALUE_SIZE_BYTES
+                .with_label_values(&["get"])
+                .observe(value.len() as f64);
+        }
+    }
+}
+
+#[async_trait]
+impl CacheBackend for InstrumentedBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        let value = self.observe("get", self.inner.get(key)).await?;
+        Self::record_lookup(value.as_deref());
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        metrics::VALUE_SIZE_BYTES
+            .with_label_values(&["set"])
+            .observe(value.len() as f64);
+        self.observe("set", self.inner.set(key, value, ttl)).await
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        metrics::VALUE_SIZE_BYTES
+            .with_label_values(&["set"])
+            .observe(value.len() as f64);
+        self.observe("set_if", self.inner.set_if(key, value, ttl, condition))
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        self.observe("delete", self.inner.delete(key)).await
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        self.observe("ttl", self.inner.ttl(key)).await
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        self.observe("set_ttl", self.inner.set_ttl(key, ttl)).await
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        self.observe("increment", self.inner.increment(key, by, ttl))
+            .await
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        self.observe("key_count", self.inner.key_count()).await
+    }
+
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.observe("scan", self.inner.scan(cursor, pattern, count))
+            .await
+    }
+
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        self.observe("tag", self.inner.tag(key, tags)).await
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.observe("tagged", self.inner.tagged(tag, cursor, count))
+            .await
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        self.observe("untag", self.inner.untag(tag, keys)).await
+    }
+
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        self.observe("watch", self.inner.watch()).await
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        self.observe("inspect_many", self.inner.inspect_many(keys))
+            .await
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        let found = self
+            .observe("get_with_ttl", self.inner.get_with_ttl(key))
+            .await?;
+        Self::record_lookup(found.as_ref().map(|(value, _)| value.as_slice()));
+        Ok(found)
+    }
+
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        let found = self.observe("lookup", self.inner.lookup(key)).await?;
+        Self::record_lookup(found.as_ref().map(|hit| hit.value.as_slice()));
+        Ok(found)
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        let found = self
+            .observe("get_many", self.inner.get_many_with_ttl(keys))
+            .await?;
+        for entry in &found {
+            Self::record_lookup(entry.as_ref().map(|(value, _)| value.as_slice()));
+        }
+        Ok(found)
+    }
+
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        for item in items {
+            metrics::VALUE_SIZE_BYTES
+                .with_label_values(&["set"])
+                .observe(item.value.len() as f64);
+        }
+        self.observe("set_many", self.inner.set_many(items)).await
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        self.inner.health().await
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        self.inner.stats()
+    }
+}
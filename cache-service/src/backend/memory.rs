@@ -0,0 +1,727 @@
+use super::{
+    glob_match, version, BackendError, BackendResult, CacheBackend, Counter, Hit, KeyEvent,
+    KeyEventKind, KeyEvents, KeyTtl, ScanPage, SetCondition,
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Bound;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Events buffered for watchers that fall behind before they miss some.
+const EVENT_BUFFER: usize = 1024;
+
+/// Limits applied to the in-process store.
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryLimits {
+    /// Maximum number of entries held at once.
+    pub max_entries: usize,
+    /// Maximum combined size of keys and values, in bytes.
+    pub max_bytes: usize,
+}
+
+struct Entry {
+    value: Vec<u8>,
+    expires_at: Option<Instant>,
+    stored_at: Instant,
+    last_used: u64,
+}
+
+impl Entry {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    fn ttl(&self, now: Instant) -> KeyTtl {
+        match self.expires_at {
+            Some(at) => KeyTtl::Expires(at - now),
+            None => KeyTtl::Persistent,
+        }
+    }
+}
+
+/// Instant a TTL starting `now` runs out; TTLs too long for an `Instant` never
+/// run out.
+fn expiry(now: Instant, ttl: Option<Duration>) -> Option<Instant> {
+    ttl.and_then(|ttl| now.checked_add(ttl))
+}
+
+struct Store {
+    entries: HashMap<String, Entry>,
+    /// Keys ordered by last use, oldest first.
+    recency: BTreeMap<u64, String>,
+    /// Keys that expire, soonest first.
+    expiries: BTreeSet<(Instant, String)>,
+    clock: u64,
+    bytes: usize,
+    /// Keys by tag. Keys that are gone are dropped when the tag is listed.
+    tags: HashMap<String, BTreeSet<String>>,
+    events: broadcast::Sender<KeyEvent>,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            expiries: BTreeSet::new(),
+            clock: 0,
+            bytes: 0,
+            tags: HashMap::new(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl Store {
+    fn notify(&self, kind: KeyEventKind, key: &str) {
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(KeyEvent {
+                kind,
+                key: key.to_string(),
+            });
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn touch(&mut self, key: &str) {
+        let tick = self.tick();
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.recency.insert(tick, key.to_string());
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.last_used);
+        if let Some(at) = entry.expires_at {
+            self.expiries.remove(&(at, key.to_string()));
+        }
+        self.bytes -= key.len() + entry.value.len();
+        Some(entry)
+    }
+
+    /// Changes when the entry for `key` expires, if there is one.
+    fn set_expiry(&mut self, key: &str, expires_at: Option<Instant>) {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return;
+        };
+        if let Some(at) = std::mem::replace(&mut entry.expires_at, expires_at) {
+            self.expiries.remove(&(at, key.to_string()));
+        }
+        if let Some(at) = expires_at {
+            self.expiries.insert((at, key.to_string()));
+        }
+    }
+
+    /// Returns the live entry for `key`, dropping it first if it has expired.
+    fn live(&mut self, key: &str, now: Instant) -> Option<&mut Entry> {
+        if self.entries.get(key)?.is_expired(now) {
+            self.remove(key);
+            self.notify(KeyEventKind::Expire, key);
+            return None;
+        }
+        self.entries.get_mut(key)
+    }
+
+    fn untag(&mut self, tag: &str, keys: &[&str]) {
+        if let Some(members) = self.tags.get_mut(tag) {
+            for key in keys {
+                members.remove(*key);
+            }
+            if members.is_empty() {
+                self.tags.remove(tag);
+            }
+        }
+    }
+
+    /// Drops the entries that have expired, visiting only those.
+    fn purge_expired(&mut self, now: Instant) {
+        while let Some((_, key)) = self.expiries.first().filter(|(at, _)| *at <= now) {
+            let key = key.clone();
+            self.remove(&key);
+            self.notify(KeyEventKind::Expire, &key);
+        }
+    }
+
+    /// Makes room for an entry of `size` bytes, purging expired entries
+    /// before evicting the least recently used ones.
+    fn reserve(&mut self, size: usize, limits: MemoryLimits, now: Instant) {
+        let fits = |store: &Store| {
+            store.entries.len() < limits.max_entries && store.bytes + size <= limits.max_bytes
+        };
+        if fits(self) {
+            return;
+        }
+        self.purge_expired(now);
+        while !fits(self) {
+            let Some((_, key)) = self.recency.first_key_value() else {
+                break;
+            };
+            let key = key.clone();
+            self.remove(&key);
+            tracing::debug!("Evicted least recently used key: {}", key);
+            self.notify(KeyEventKind::Evict, &key);
+        }
+    }
+}
+
+/// Backend keeping entries in process memory, for running without Redis.
+///
+/// Expired entries are dropped when read and purged whenever the store
+/// needs room; beyond that, the least recently used entries are evicted.
+pub struct MemoryBackend {
+    limits: MemoryLimits,
+    store: Mutex<Store>,
+}
+
+impl MemoryBackend {
+    pub fn new(limits: MemoryLimits) -> Self {
+        Self {
+            limits,
+            store: Mutex::new(Store::default()),
+        }
+    }
+
+    /// Number of entries currently held, including any not yet purged.
+    pub fn len(&self) -> usize {
+        self.store.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn check_size(&self, key: &str, value: &[u8]) -> BackendResult<()> {
+        let size = key.len() + value.len();
+        if size > self.limits.max_bytes {
+            return Err(BackendError::EntryTooLarge {
+                size,
+                limit: self.limits.max_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Replaces the entry for `key`, making room for it first.
+    fn insert(
+        &self,
+        store: &mut Store,
+        key: &str,
+        value: &[u8],
+        expires_at: Option<Instant>,
+        now: Instant,
+    ) {
+        let size = key.len() + value.len();
+        store.remove(key);
+        store.reserve(size, self.limits, now);
+
+        let tick = store.tick();
+        store.entries.insert(
+            key.to_string(),
+            Entry {
+                value: value.to_vec(),
+                expires_at,
+                stored_at: now,
+                last_used: tick,
+            },
+        );
+        store.recency.insert(tick, key.to_string());
+        if let Some(at) = expires_at {
+            store.expiries.insert((at, key.to_string()));
+        }
+        store.bytes += size;
+        store.notify(KeyEventKind::Set, key);
+    }
+}
+
+#[async_trait]
+impl CacheBackend for MemoryBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        let mut store = self.store.lock().unwrap();
+        let value = store
+            .live(key, Instant::now())
+            .map(|entry| entry.value.clone());
+        if value.is_some() {
+            store.touch(key);
+        }
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        self.check_size(key, value)?;
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        self.insert(&mut store, key, value, expiry(now, ttl), now);
+        Ok(())
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        self.check_size(key, value)?;
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        let current = store.live(key, now).map(|entry| entry.value.as_slice());
+        let holds = match condition {
+            SetCondition::Absent => current.is_none(),
+            SetCondition::Present => current.is_some(),
+            SetCondition::Version(expected) => current.is_some_and(|v| version(v) == *expected),
+        };
+        if holds {
+            self.insert(&mut store, key, value, expiry(now, ttl), now);
+        }
+        Ok(holds)
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        let (value, expires_at) = match store.live(key, now) {
+            Some(entry) => (by.add_to(Some(&entry.value)), entry.expires_at),
+            None => (by.add_to(None), expiry(now, ttl)),
+        };
+        let value = value.ok_or_else(|| BackendError::NotCounter(key.to_string()))?;
+        let stored = value.to_string().into_bytes();
+        self.check_size(key, &stored)?;
+        self.insert(&mut store, key, &stored, expires_at, now);
+        Ok(value)
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        let mut store = self.store.lock().unwrap();
+        let existed = store.live(key, Instant::now()).is_some();
+        store.remove(key);
+        if existed {
+            store.notify(KeyEventKind::Delete, key);
+        }
+        Ok(existed)
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        Ok(match store.live(key, now) {
+            None => KeyTtl::Missing,
+            Some(entry) => entry.ttl(now),
+        })
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        if store.live(key, now).is_none() {
+            return Ok(false);
+        }
+        store.set_expiry(key, expiry(now, ttl));
+        Ok(true)
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        let found = store
+            .live(key, now)
+            .map(|entry| (entry.value.clone(), entry.ttl(now)));
+        if found.is_some() {
+            store.touch(key);
+        }
+        Ok(found)
+    }
+
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        let now = Instant::now();
+        let mut store = self.store.lock().unwrap();
+        let found = store.live(key, now).map(|entry| Hit {
+            value: entry.value.clone(),
+            ttl: entry.ttl(now),
+            age: now - entry.stored_at,
+        });
+        if found.is_some() {
+            store.touch(key);
+        }
+        Ok(found)
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        Ok(self.len() as u64)
+    }
+
+    /// Lists keys in order, with the last key of a page as the cursor of the
+    /// next one.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let now = Instant::now();
+        let count = count.max(1);
+        let store = self.store.lock().unwrap();
+        let mut keys: Vec<&String> = store
+            .entries
+            .iter()
+            .filter(|(key, entry)| {
+                cursor.is_none_or(|cursor| key.as_str() > cursor)
+                    && !entry.is_expired(now)
+                    && glob_match(pattern, key)
+            })
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort_unstable();
+        let more = keys.len() > count;
+        let keys: Vec<String> = keys.into_iter().take(count).cloned().collect();
+        Ok(ScanPage {
+            cursor: more.then(|| keys.last().cloned()).flatten(),
+            keys,
+        })
+    }
+
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        let mut store = self.store.lock().unwrap();
+        for tag in tags {
+            store
+                .tags
+                .entry(tag.to_string())
+                .or_default()
+                .insert(key.to_string());
+        }
+        Ok(())
+    }
+
+    /// Lists tagged keys in order, like [`MemoryBackend::scan`].
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let now = Instant::now();
+        let count = count.max(1);
+        let mut store = self.store.lock().unwrap();
+        let Some(members) = store.tags.get(tag) else {
+            return Ok(ScanPage::default());
+        };
+        let after = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+        let mut window: Vec<String> = members
+            .range::<str, _>((after, Bound::Unbounded))
+            .take(count + 1)
+            .cloned()
+            .collect();
+        let more = window.len() > count;
+        window.truncate(count);
+        let cursor = more.then(|| window.last().cloned()).flatten();
+        let (keys, gone): (Vec<String>, Vec<String>) = window.into_iter().partition(|key| {
+            store
+                .entries
+                .get(key)
+                .is_some_and(|entry| !entry.is_expired(now))
+        });
+        let members: Vec<&str> = gone.iter().map(String::as_str).collect();
+        store.untag(tag, &members);
+        Ok(ScanPage { keys, cursor })
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        self.store.lock().unwrap().untag(tag, keys);
+        Ok(())
+    }
+
+    /// Expiry is noticed lazily, when an expired key is read or purged.
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        let events = self.store.lock().unwrap().events.subscribe();
+        Ok(futures::stream::unfold(events, |mut events| async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => return Some((event, events)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Watcher missed {} key events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed())
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        let store = self.store.lock().unwrap();
+        serde_json::json!({
+            "entries": store.entries.len(),
+            "bytes": store.bytes,
+            "max_entries": self.limits.max_entries,
+            "max_bytes": self.limits.max_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backend(max_entries: usize, max_bytes: usize) -> MemoryBackend {
+        MemoryBackend::new(MemoryLimits {
+            max_entries,
+            max_bytes,
+        })
+    }
+
+    #[tokio::test]
+    async fn set_then_get_round_trips() {
+        let cache = backend(10, 1024);
+        cache.set("a", b"1", None).await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(cache.ttl("a").await.unwrap(), KeyTtl::Persistent);
+        assert_eq!(cache.get("b").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_ttl() {
+        let cache = backend(10, 1024);
+        cache
+            .set("a", b"1", Some(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        assert!(matches!(cache.ttl("a").await.unwrap(), KeyTtl::Expires(_)));
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        assert_eq!(cache.ttl("a").await.unwrap(), KeyTtl::Missing);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn evicts_least_recently_used_when_entry_budget_is_full() {
+        let cache = backend(2, 1024);
+        cache.set("a", b"1", None).await.unwrap();
+        cache.set("b", b"2", None).await.unwrap();
+        cache.get("a").await.unwrap();
+        cache.set("c", b"3", None).await.unwrap();
+
+        assert_eq!(cache.get("b").await.unwrap(), None);
+        assert!(cache.get("a").await.unwrap().is_some());
+        assert!(cache.get("c").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn byte_budget_prefers_purging_expired_entries() {
+        let cache = backend(10, 8);
+        cache
+            .set("a", b"111", Some(Duration::from_millis(10)))
+            .await
+            .unwrap();
+        cache.set("b", b"222", None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.set("c", b"333", None).await.unwrap();
+
+        assert!(cache.get("b").await.unwrap().is_some());
+        assert!(cache.get("c").await.unwrap().is_some());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejects_entries_larger_than_budget() {
+        let cache = backend(10, 4);
+        let err = cache.set("key", b"value", None).await.unwrap_err();
+        assert!(matches!(err, BackendError::EntryTooLarge { .. }));
+    }
+
+    #[tokio::test]
+    async fn delete_reports_existence() {
+        let cache = backend(10, 1024);
+        cache.set("a", b"1", None).await.unwrap();
+        assert!(cache.delete("a").await.unwrap());
+        assert!(!cache.delete("a").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn set_ttl_extends_and_persists_existing_keys() {
+        let cache = backend(10, 1024);
+        cache
+            .set("a", b"1", Some(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        assert!(cache
+            .set_ttl("a", Some(Duration::from_secs(60)))
+            .await
+            .unwrap());
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(cache.get("a").await.unwrap().is_some());
+
+        assert!(cache.set_ttl("a", None).await.unwrap());
+        assert_eq!(cache.ttl("a").await.unwrap(), KeyTtl::Persistent);
+        assert!(!cache.set_ttl("b", None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ttls_beyond_the_clock_never_expire() {
+        let cache = backend(10, 1024);
+        let forever = Some(Duration::from_secs(u64::MAX));
+        cache.set("a", b"1", forever).await.unwrap();
+        assert!(cache
+            .set_if("b", b"1", forever, &SetCondition::Absent)
+            .await
+            .unwrap());
+        cache
+            .increment("n", Counter::Integer(1), forever)
+            .await
+            .unwrap();
+        assert!(cache.set_ttl("a", forever).await.unwrap());
+        for key in ["a", "b", "n"] {
+            assert_eq!(cache.ttl(key).await.unwrap(), KeyTtl::Persistent);
+        }
+    }
+
+    #[tokio::test]
+    async fn purging_follows_changed_expiries() {
+        let cache = backend(2, 1024);
+        let short = Some(Duration::from_millis(10));
+        cache.set("a", b"1", short).await.unwrap();
+        assert!(cache.set_ttl("a", None).await.unwrap());
+        cache.set("b", b"2", None).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cache.get("a").await.unwrap().is_some());
+
+        // Nothing has expired, so the least recently used key makes room.
+        cache.set("c", b"3", None).await.unwrap();
+        assert!(cache.get("a").await.unwrap().is_some());
+        assert_eq!(cache.get("b").await.unwrap(), None);
+        assert!(cache.store.lock().unwrap().expiries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_if_checks_the_condition() {
+        let cache = backend(10, 1024);
+        let (absent, present) = (SetCondition::Absent, SetCondition::Present);
+        assert!(!cache.set_if("a", b"1", None, &present).await.unwrap());
+        assert!(cache.set_if("a", b"1", None, &absent).await.unwrap());
+        assert!(!cache.set_if("a", b"2", None, &absent).await.unwrap());
+
+        let stale = SetCondition::Version(version(b"0"));
+        assert!(!cache.set_if("a", b"2", None, &stale).await.unwrap());
+        let current = SetCondition::Version(version(b"1"));
+        assert!(cache.set_if("a", b"2", None, &current).await.unwrap());
+        assert!(!cache.set_if("a", b"3", None, &current).await.unwrap());
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn increments_counters_like_redis() {
+        let cache = backend(10, 1024);
+        let ttl = Some(Duration::from_secs(60));
+        let one = Counter::Integer(1);
+        assert_eq!(cache.increment("n", one, ttl).await.unwrap(), one);
+        assert_eq!(
+            cache
+                .increment("n", Counter::Integer(-5), None)
+                .await
+                .unwrap(),
+            Counter::Integer(-4)
+        );
+        assert!(matches!(cache.ttl("n").await.unwrap(), KeyTtl::Expires(_)));
+        assert_eq!(
+            cache
+                .increment("n", Counter::Float(0.5), None)
+                .await
+                .unwrap(),
+            Counter::Float(-3.5)
+        );
+        assert_eq!(cache.get("n").await.unwrap(), Some(b"-3.5".to_vec()));
+        assert_eq!(
+            cache.increment("n", one, None).await.unwrap(),
+            Counter::Float(-2.5)
+        );
+
+        cache
+            .set("max", i64::MAX.to_string().as_bytes(), None)
+            .await
+            .unwrap();
+        assert!(cache.increment("max", one, None).await.is_err());
+        cache.set("text", b"abc", None).await.unwrap();
+        assert!(cache
+            .increment("text", Counter::Float(1.0), None)
+            .await
+            .is_err());
+        assert!(cache.increment("text", one, None).await.is_err());
+        assert_eq!(cache.ttl("new").await.unwrap(), KeyTtl::Missing);
+        cache.increment("new", one, None).await.unwrap();
+        assert_eq!(cache.ttl("new").await.unwrap(), KeyTtl::Persistent);
+    }
+
+    #[tokio::test]
+    async fn scan_pages_through_matching_keys() {
+        let cache = backend(10, 1024);
+        for key in ["user:1", "user:2", "user:3", "session:1"] {
+            cache.set(key, b"1", None).await.unwrap();
+        }
+        cache
+            .set("user:4", b"1", Some(Duration::from_millis(1)))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        let first = cache.scan(None, "user:*", 2).await.unwrap();
+        assert_eq!(first.keys, vec!["user:1", "user:2"]);
+        let cursor = first.cursor.expect("more keys to list");
+        let second = cache.scan(Some(&cursor), "user:*", 2).await.unwrap();
+        assert_eq!(second.keys, vec!["user:3"]);
+        assert_eq!(second.cursor, None);
+    }
+
+    #[tokio::test]
+    async fn watchers_see_sets_deletes_expiry_and_eviction() {
+        let cache = backend(2, 1024);
+        let mut events = cache.watch().await.unwrap();
+        cache.set("a", b"1", None).await.unwrap();
+        cache
+            .set("b", b"1", Some(Duration::from_millis(1)))
+            .await
+            .unwrap();
+        cache.delete("a").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(cache.get("b").await.unwrap(), None);
+        cache.set("c", b"1", None).await.unwrap();
+        cache.set("d", b"1", None).await.unwrap();
+        cache.set("e", b"1", None).await.unwrap();
+
+        let mut seen = Vec::new();
+        for _ in 0..7 {
+            let event = events.next().await.unwrap();
+            seen.push((event.kind, event.key));
+        }
+        let expected = [
+            (KeyEventKind::Set, "a"),
+            (KeyEventKind::Set, "b"),
+            (KeyEventKind::Delete, "a"),
+            (KeyEventKind::Expire, "b"),
+            (KeyEventKind::Set, "c"),
+            (KeyEventKind::Set, "d"),
+            (KeyEventKind::Evict, "c"),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(k, key)| (*k, key.to_string()))
+            .collect();
+        assert_eq!(seen, expected);
+    }
+}
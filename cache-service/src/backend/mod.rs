@@ -0,0 +1,478 @@
+mod breaker;
+mod cluster;
+mod connection;
+mod glob;
+mod instrumented;
+mod memory;
+mod namespaced;
+mod redis;
+mod ring;
+mod sentinel;
+mod sharded;
+mod tiered;
+
+pub use self::breaker::BreakerBackend;
+pub use self::cluster::{ClusterBackend, ClusterConnector};
+pub use self::connection::{
+    Connect, ConnectionSettings, ConnectionState, RedisConnection, ScanCursor,
+};
+pub use self::glob::{escape_glob, glob_match};
+pub use self::instrumented::InstrumentedBackend;
+pub use self::memory::{MemoryBackend, MemoryLimits};
+pub use self::namespaced::{
+    namespace_prefix, NamespacedBackend, NAMESPACE_PREFIX, TAG_INDEX_PREFIX,
+};
+pub use self::redis::RedisBackend;
+pub use self::ring::HashRing;
+pub use self::sentinel::{SentinelBackend, SentinelConnection, SentinelConnector};
+pub use self::sharded::ShardedBackend;
+pub use self::tiered::TieredBackend;
+
+use async_trait::async_trait;
+use futures::stream::{self, BoxStream};
+use futures::{future, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Errors reported by a cache backend.
+#[derive(Debug, thiserror::Error)]
+pub enum BackendError {
+    #[error("redis error: {0}")]
+    Redis(#[from] ::redis::RedisError),
+    #[error("backend did not respond within {0:?}")]
+    Timeout(Duration),
+    #[error("entry of {size} bytes exceeds the cache budget of {limit} bytes")]
+    EntryTooLarge { size: usize, limit: usize },
+    #[error("circuit breaker '{breaker}' is open, retry in {retry_after:?}")]
+    CircuitOpen {
+        breaker: String,
+        retry_after: Duration,
+    },
+    #[error("key {key:?} is under the reserved prefix {prefix:?}")]
+    ReservedKey { key: String, prefix: &'static str },
+    #[error("invalid scan cursor {0:?}")]
+    InvalidCursor(String),
+    #[error("value of key {0:?} is not a number, or incrementing it would overflow")]
+    NotCounter(String),
+    #[error("hash ring has no shards to route keys to")]
+    EmptyRing,
+}
+
+impl BackendError {
+    /// Short label of the error, used in metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BackendError::Redis(_) => "redis",
+            BackendError::Timeout(_) => "timeout",
+            BackendError::EntryTooLarge { .. } => "entry_too_large",
+            BackendError::CircuitOpen { .. } => "circuit_open",
+            BackendError::ReservedKey { .. } => "reserved_key",
+            BackendError::InvalidCursor(_) => "invalid_cursor",
+            BackendError::NotCounter(_) => "not_counter",
+            BackendError::EmptyRing => "empty_ring",
+        }
+    }
+
+    /// Whether the error means the storage could not be reached, as opposed
+    /// to it rejecting this particular request.
+    pub fn is_unavailable(&self) -> bool {
+        use ::redis::ErrorKind;
+
+        match self {
+            BackendError::Timeout(_)
+            | BackendError::CircuitOpen { .. }
+            | BackendError::EmptyRing => true,
+            BackendError::Redis(e) => {
+                e.is_io_error()
+                    || e.is_timeout()
+                    || e.is_connection_dropped()
+                    || e.is_connection_refusal()
+                    || matches!(
+                        e.kind(),
+                        ErrorKind::BusyLoadingError
+                            | ErrorKind::TryAgain
+                            | ErrorKind::ClusterDown
+                            | ErrorKind::MasterDown
+                            | ErrorKind::MasterNameNotFoundBySentinel
+                            | ErrorKind::EmptySentinelList
+                    )
+            }
+            BackendError::EntryTooLarge { .. }
+            | BackendError::ReservedKey { .. }
+            | BackendError::InvalidCursor(_)
+            | BackendError::NotCounter(_) => false,
+        }
+    }
+}
+
+pub type BackendResult<T> = Result<T, BackendError>;
+
+/// Remaining lifetime of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyTtl {
+    /// The key does not exist.
+    Missing,
+    /// The key exists and never expires.
+    Persistent,
+    /// The key exists and expires after the given duration.
+    Expires(Duration),
+}
+
+/// A value found by [`CacheBackend::lookup`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub value: Vec<u8>,
+    pub ttl: KeyTtl,
+    /// Time since the value was written or fetched from the backing store,
+    /// zero when the backend does not know.
+    pub age: Duration,
+}
+
+/// Entry of a [`CacheBackend::set_many`] batch.
+#[derive(Debug, Clone, Copy)]
+pub struct SetItem<'a> {
+    pub key: &'a str,
+    pub value: &'a [u8],
+    pub ttl: Option<Duration>,
+}
+
+/// Keys found by one [`CacheBackend::scan`] step.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanPage {
+    pub keys: Vec<String>,
+    /// Cursor to resume from, `None` once every key was visited.
+    pub cursor: Option<String>,
+}
+
+/// Value of a counter, or an amount it is incremented by.
+///
+/// Counters are stored as plain decimal strings, like Redis `INCRBY` and
+/// `INCRBYFLOAT` store them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Counter {
+    Integer(i64),
+    Float(f64),
+}
+
+impl Counter {
+    /// Adds `self` to the counter stored as `current`, a missing counter
+    /// counting as zero. Like Redis, integers are added to a counter holding
+    /// a fraction as floats. `None` when `current` is not a number or the
+    /// sum overflows.
+    pub fn add_to(self, current: Option<&[u8]>) -> Option<Counter> {
+        let current = match current {
+            Some(bytes) => Some(std::str::from_utf8(bytes).ok()?),
+            None => None,
+        };
+        let add_float = |by: f64| {
+            let current = current.map_or(Ok(0.0), str::parse::<f64>).ok()?;
+            let sum = current + by;
+            (current.is_finite() && sum.is_finite()).then_some(Counter::Float(sum))
+        };
+        match self {
+            Counter::Integer(by) => match current.map_or(Ok(0), str::parse::<i64>) {
+                Ok(current) => current.checked_add(by).map(Counter::Integer),
+                Err(_) => add_float(by as f64),
+            },
+            Counter::Float(by) => add_float(by),
+        }
+    }
+
+    /// The opposite amount, `None` if it overflows.
+    pub fn checked_neg(self) -> Option<Counter> {
+        match self {
+            Counter::Integer(n) => n.checked_neg().map(Counter::Integer),
+            Counter::Float(n) => Some(Counter::Float(-n)),
+        }
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Counter::Integer(n) => write!(f, "{}", n),
+            Counter::Float(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+/// Change made to a key by any client of the storage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyEvent {
+    #[serde(rename = "type")]
+    pub kind: KeyEventKind,
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyEventKind {
+    Set,
+    Delete,
+    Expire,
+    /// The key was removed to make room for others.
+    Evict,
+}
+
+impl KeyEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KeyEventKind::Set => "set",
+            KeyEventKind::Delete => "delete",
+            KeyEventKind::Expire => "expire",
+            KeyEventKind::Evict => "evict",
+        }
+    }
+}
+
+/// Events of [`CacheBackend::watch`]; ends when the subscription is lost.
+pub type KeyEvents = BoxStream<'static, KeyEvent>;
+
+/// Merges the events of several subscriptions into one stream that ends as
+/// soon as any of them does, so that the subscriber notices and subscribes
+/// again rather than missing the events of the lost one.
+pub(crate) fn merge_events(subscriptions: Vec<KeyEvents>) -> KeyEvents {
+    let subscriptions = subscriptions
+        .into_iter()
+        .map(|events| events.map(Some).chain(stream::once(future::ready(None))));
+    stream::select_all(subscriptions)
+        .take_while(|event| future::ready(event.is_some()))
+        .filter_map(future::ready)
+        .boxed()
+}
+
+/// Lifetime and size of a key, as reported by [`CacheBackend::inspect_many`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyInfo {
+    pub ttl: KeyTtl,
+    /// Size of the value in bytes, as stored.
+    pub size: usize,
+}
+
+/// Precondition of a [`CacheBackend::set_if`] write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SetCondition {
+    /// The key must not exist.
+    Absent,
+    /// The key must exist.
+    Present,
+    /// The key must hold a value with this [`version`].
+    Version(String),
+}
+
+/// Version of a stored value: the hex SHA-1 of its bytes, as computed by
+/// Redis' `redis.sha1hex` so scripts can compare it server-side.
+pub fn version(value: &[u8]) -> String {
+    sha1_smol::Sha1::from(value).digest().to_string()
+}
+
+/// Storage used by the cache handlers.
+///
+/// Values are opaque bytes; encoding them is the caller's concern.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+    /// Returns the value stored under `key`, or `None` on a miss.
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>>;
+
+    /// Stores `value` under `key`, expiring after `ttl` if one is given.
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()>;
+
+    /// Stores `value` under `key` only if `condition` holds, atomically,
+    /// returning whether it was written.
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool>;
+
+    /// Removes `key`, returning whether it existed.
+    async fn delete(&self, key: &str) -> BackendResult<bool>;
+
+    /// Returns the remaining lifetime of `key`.
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl>;
+
+    /// Makes an existing `key` expire after `ttl`, or never with `None`,
+    /// returning whether the key exists.
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool>;
+
+    /// Adds `by` to the counter stored under `key` atomically, creating it
+    /// from zero to expire after `ttl` if it does not exist, and returns the
+    /// new value. The expiry of an existing counter is left as is.
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter>;
+
+    /// Returns the number of keys currently stored.
+    async fn key_count(&self) -> BackendResult<u64>;
+
+    /// Lists keys matching the glob `pattern`, visiting about `count` keys
+    /// from `cursor` (`None` to start over).
+    ///
+    /// Like Redis `SCAN`, a page may hold fewer keys than `count` without the
+    /// iteration being over, and keys that exist throughout the iteration are
+    /// returned at least once.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage>;
+
+    /// Adds `key` to the index of each of `tags`. Indexes do not expire with
+    /// their keys; keys are removed from them with [`CacheBackend::untag`].
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()>;
+
+    /// Lists about `count` keys indexed under `tag` from `cursor`, with the
+    /// guarantees of [`CacheBackend::scan`]. Listed keys may have expired or
+    /// been deleted since they were tagged.
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage>;
+
+    /// Removes `keys` from the index of `tag`.
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()>;
+
+    /// Subscribes to the changes made to keys by every client of the
+    /// storage, including other replicas of the service.
+    async fn watch(&self) -> BackendResult<KeyEvents>;
+
+    /// Returns the value stored under `key` together with its remaining lifetime.
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        match self.get(key).await? {
+            Some(value) => Ok(Some((value, self.ttl(key).await?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value stored under `key` with its lifetime and age.
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        Ok(self.get_with_ttl(key).await?.map(|(value, ttl)| Hit {
+            value,
+            ttl,
+            age: Duration::ZERO,
+        }))
+    }
+
+    /// Looks up several keys in one round trip where the backend allows it,
+    /// returning each value with its remaining lifetime in the order of `keys`.
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        let mut found = Vec::with_capacity(keys.len());
+        for key in keys {
+            found.push(self.get_with_ttl(key).await?);
+        }
+        Ok(found)
+    }
+
+    /// Returns the lifetime and size of several keys without transferring
+    /// their values where the backend allows it, in the order of `keys`.
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        Ok(self
+            .get_many_with_ttl(keys)
+            .await?
+            .into_iter()
+            .map(|entry| {
+                entry.map(|(value, ttl)| KeyInfo {
+                    ttl,
+                    size: value.len(),
+                })
+            })
+            .collect())
+    }
+
+    /// Stores several entries in one round trip where the backend allows it.
+    ///
+    /// An error means the batch as a whole failed; otherwise every item
+    /// gets its own result, in the order of `items`.
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            results.push(self.set(item.key, item.value, item.ttl).await);
+        }
+        Ok(results)
+    }
+
+    /// Reachability of the storage, reported by `/health`.
+    async fn health(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    /// Backend-specific diagnostics, reported by `/admin/stats`.
+    fn stats(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+}
+
+/// Reassembles the replies of a batch split into groups of indices back
+/// into the order of the original batch.
+pub(crate) fn scatter<T>(
+    len: usize,
+    groups: impl IntoIterator<Item = (Vec<usize>, Vec<T>)>,
+) -> Vec<T> {
+    let mut slots: Vec<Option<T>> = (0..len).map(|_| None).collect();
+    for (indices, replies) in groups {
+        for (index, reply) in indices.into_iter().zip(replies) {
+            slots[index] = Some(reply);
+        }
+    }
+    slots
+        .into_iter()
+        .map(|slot| slot.expect("every item belongs to one group"))
+        .collect()
+}
+
+/// Writes the `group` of `items` one by one, reporting each write's result.
+///
+/// Used when a batch failed as a whole, which does not tell which items went
+/// through; writes that did are only repeated.
+pub(crate) async fn write_each(
+    backend: &dyn CacheBackend,
+    group: &[usize],
+    items: &[SetItem<'_>],
+) -> Vec<BackendResult<()>> {
+    future::join_all(group.iter().map(|&index| {
+        let item = &items[index];
+        backend.set(item.key, item.value, item.ttl)
+    }))
+    .await
+}
+
+impl KeyTtl {
+    /// Interprets a Redis `PTTL` reply.
+    pub(crate) fn from_pttl(ms: i64) -> Self {
+        match ms {
+            -2 => KeyTtl::Missing,
+            ms if ms < 0 => KeyTtl::Persistent,
+            ms => KeyTtl::Expires(Duration::from_millis(ms as u64)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn merged_events_end_with_any_subscription() {
+        let event = |key: &str| KeyEvent {
+            kind: KeyEventKind::Set,
+            key: key.to_string(),
+        };
+        let lost = stream::iter([event("a")]).boxed();
+        let open = stream::pending().boxed();
+        let merged: Vec<KeyEvent> = merge_events(vec![open, lost]).collect().await;
+        assert_eq!(merged, vec![event("a")]);
+    }
+}
@@ -0,0 +1,307 @@
+use super::{
+    escape_glob, BackendError, BackendResult, CacheBackend, Counter, Hit, KeyEvent, KeyEvents,
+    KeyInfo, KeyTtl, ScanPage, SetCondition, SetItem,
+};
+use async_trait::async_trait;
+use futures::future;
+use futures::StreamExt;
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Prefix under which the keys of every namespace but the default are stored.
+pub const NAMESPACE_PREFIX: &str = "ns:";
+
+/// Prefix of the sets indexing tagged keys in Redis. Namespace names cannot
+/// contain `#`, so no cache key is ever stored under it.
+pub const TAG_INDEX_PREFIX: &str = "ns:#tag:";
+
+/// Prefix of the keys of a namespace other than the default one.
+pub fn namespace_prefix(namespace: &str) -> String {
+    format!("{}{}:", NAMESPACE_PREFIX, namespace)
+}
+
+/// Backend confining keys to a namespace by prefixing them with
+/// `ns:<name>:`.
+///
+/// The default namespace is not prefixed, so keys written before namespaces
+/// existed stay readable; it rejects keys under [`NAMESPACE_PREFIX`] instead,
+/// so it cannot reach into other namespaces.
+pub struct NamespacedBackend {
+    inner: Arc<dyn CacheBackend>,
+    prefix: Option<String>,
+}
+
+impl NamespacedBackend {
+    pub fn new(inner: Arc<dyn CacheBackend>, namespace: &str) -> Self {
+        Self {
+            inner,
+            prefix: Some(namespace_prefix(namespace)),
+        }
+    }
+
+    /// The unprefixed default namespace.
+    pub fn root(inner: Arc<dyn CacheBackend>) -> Self {
+        Self {
+            inner,
+            prefix: None,
+        }
+    }
+
+    fn key<'a>(&self, key: &'a str) -> BackendResult<Cow<'a, str>> {
+        match &self.prefix {
+            Some(prefix) => Ok(Cow::Owned(format!("{}{}", prefix, key))),
+            None if key.starts_with(NAMESPACE_PREFIX) => Err(BackendError::ReservedKey {
+                key: key.to_string(),
+                prefix: NAMESPACE_PREFIX,
+            }),
+            None => Ok(Cow::Borrowed(key)),
+        }
+    }
+
+    fn keys<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> BackendResult<Vec<Cow<'a, str>>> {
+        keys.into_iter().map(|key| self.key(key)).collect()
+    }
+}
+
+#[async_trait]
+impl CacheBackend for NamespacedBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        self.inner.get(&self.key(key)?).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        self.inner.set(&self.key(key)?, value, ttl).await
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        self.inner
+            .set_if(&self.key(key)?, value, ttl, condition)
+            .await
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        self.inner.delete(&self.key(key)?).await
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        self.inner.ttl(&self.key(key)?).await
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        self.inner.set_ttl(&self.key(key)?, ttl).await
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        self.inner.increment(&self.key(key)?, by, ttl).await
+    }
+
+    /// Counts the keys of every namespace.
+    async fn key_count(&self) -> BackendResult<u64> {
+        self.inner.key_count().await
+    }
+
+    /// Lists the keys of the namespace, without their prefix.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let Some(prefix) = &self.prefix else {
+            let mut page = self.inner.scan(cursor, pattern, count).await?;
+            page.keys.retain(|key| !key.starts_with(NAMESPACE_PREFIX));
+            return Ok(page);
+        };
+        let pattern = format!("{}{}", escape_glob(prefix), pattern);
+        let page = self.inner.scan(cursor, &pattern, count).await?;
+        Ok(ScanPage {
+            keys: page
+                .keys
+                .iter()
+                .filter_map(|key| key.strip_prefix(prefix.as_str()))
+                .map(str::to_string)
+                .collect(),
+            cursor: page.cursor,
+        })
+    }
+
+    /// Tags are confined to the namespace like keys, so namespaces can use
+    /// the same tag names.
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        let tags = self.keys(tags.iter().copied())?;
+        let tags: Vec<&str> = tags.iter().map(|tag| tag.as_ref()).collect();
+        self.inner.tag(&self.key(key)?, &tags).await
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let mut page = self.inner.tagged(&self.key(tag)?, cursor, count).await?;
+        if let Some(prefix) = &self.prefix {
+            page.keys = page
+                .keys
+                .iter()
+                .filter_map(|key| key.strip_prefix(prefix.as_str()))
+                .map(str::to_string)
+                .collect();
+        }
+        Ok(page)
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        let keys = self.keys(keys.iter().copied())?;
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_ref()).collect();
+        self.inner.untag(&self.key(tag)?, &keys).await
+    }
+
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        let prefix = self.prefix.clone();
+        let events = self.inner.watch().await?;
+        Ok(events
+            .filter_map(move |event| {
+                let key = match &prefix {
+                    Some(prefix) => event.key.strip_prefix(prefix.as_str()).map(str::to_string),
+                    None => (!event.key.starts_with(NAMESPACE_PREFIX)).then_some(event.key),
+                };
+                future::ready(key.map(|key| KeyEvent { key, ..event }))
+            })
+            .boxed())
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        self.inner.get_with_ttl(&self.key(key)?).await
+    }
+
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        self.inner.lookup(&self.key(key)?).await
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        let keys = self.keys(keys.iter().copied())?;
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_ref()).collect();
+        self.inner.get_many_with_ttl(&keys).await
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        let keys = self.keys(keys.iter().copied())?;
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_ref()).collect();
+        self.inner.inspect_many(&keys).await
+    }
+
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        let keys = self.keys(items.iter().map(|item| item.key))?;
+        let items: Vec<SetItem> = items
+            .iter()
+            .zip(&keys)
+            .map(|(item, key)| SetItem { key, ..*item })
+            .collect();
+        self.inner.set_many(&items).await
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        self.inner.health().await
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        self.inner.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{KeyEventKind, MemoryBackend, MemoryLimits};
+
+    #[tokio::test]
+    async fn namespaces_do_not_share_keys() {
+        let shared: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let root = NamespacedBackend::root(shared.clone());
+        let billing = NamespacedBackend::new(shared.clone(), "billing");
+        root.set("user:1", b"root", None).await.unwrap();
+        billing.set("user:1", b"billing", None).await.unwrap();
+
+        assert_eq!(root.get("user:1").await.unwrap(), Some(b"root".to_vec()));
+        assert_eq!(
+            billing.get("user:1").await.unwrap(),
+            Some(b"billing".to_vec())
+        );
+        assert_eq!(
+            shared.get("ns:billing:user:1").await.unwrap(),
+            Some(b"billing".to_vec())
+        );
+        assert!(matches!(
+            root.get("ns:billing:user:1").await,
+            Err(BackendError::ReservedKey { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn scans_list_only_the_keys_of_the_namespace() {
+        let shared: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let root = NamespacedBackend::root(shared.clone());
+        let billing = NamespacedBackend::new(shared.clone(), "billing");
+        root.set("user:1", b"root", None).await.unwrap();
+        billing.set("user:1", b"billing", None).await.unwrap();
+        billing.set("user:2", b"billing", None).await.unwrap();
+
+        let page = billing.scan(None, "user:*", 10).await.unwrap();
+        assert_eq!(page.keys, vec!["user:1", "user:2"]);
+        let page = root.scan(None, "*", 10).await.unwrap();
+        assert_eq!(page.keys, vec!["user:1"]);
+        let info = billing.inspect_many(&["user:2", "user:3"]).await.unwrap();
+        assert_eq!(info[0].as_ref().map(|info| info.size), Some(7));
+        assert!(info[1].is_none());
+    }
+
+    #[tokio::test]
+    async fn watchers_see_only_their_namespace() {
+        let shared: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let root = NamespacedBackend::root(shared.clone());
+        let billing = NamespacedBackend::new(shared.clone(), "billing");
+        let mut root_events = root.watch().await.unwrap();
+        let mut billing_events = billing.watch().await.unwrap();
+        billing.set("user:1", b"billing", None).await.unwrap();
+        root.set("user:2", b"root", None).await.unwrap();
+
+        let event = billing_events.next().await.unwrap();
+        assert_eq!(
+            (event.kind, event.key.as_str()),
+            (KeyEventKind::Set, "user:1")
+        );
+        let event = root_events.next().await.unwrap();
+        assert_eq!(
+            (event.kind, event.key.as_str()),
+            (KeyEventKind::Set, "user:2")
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use super::{
+    merge_events, scatter, write_each, BackendError, BackendResult, CacheBackend, Connect,
+    ConnectionSettings, Counter, KeyEvent, KeyEventKind, KeyEvents, KeyInfo, KeyTtl,
+    RedisConnection, ScanCursor, ScanPage, SetCondition, SetItem, TAG_INDEX_PREFIX,
+};
+use async_trait::async_trait;
+use futures::future::{self, try_join_all};
+use futures::StreamExt;
+use redis::{AsyncCommands, Client, ErrorKind, Msg, RedisError, RedisResult, Script};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+/// Replaces `KEYS[1]` only if the SHA-1 of its value is `ARGV[1]`, expiring
+/// it after `ARGV[3]` milliseconds unless that is 0.
+static SET_IF_VERSION: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local current = redis.call('GET', KEYS[1])
+        if not current or redis.sha1hex(current) ~= ARGV[1] then
+            return 0
+        end
+        if ARGV[3] == '0' then
+            redis.call('SET', KEYS[1], ARGV[2])
+        else
+            redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+        end
+        return 1
+        ",
+    )
+});
+
+/// Adds the integer `ARGV[1]` to the counter `KEYS[1]`, creating it with an
+/// expiry of `ARGV[2]` milliseconds unless that is 0. A counter holding a
+/// fraction is added to with `INCRBYFLOAT`, and the reply says which was used.
+static INCREMENT_INTEGER: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if ARGV[2] == '0' then
+            redis.call('SET', KEYS[1], 0, 'NX')
+        else
+            redis.call('SET', KEYS[1], 0, 'NX', 'PX', ARGV[2])
+        end
+        if string.find(redis.call('GET', KEYS[1]), '^-?%d+$') then
+            return {'integer', redis.call('INCRBY', KEYS[1], ARGV[1])}
+        end
+        return {'float', redis.call('INCRBYFLOAT', KEYS[1], ARGV[1])}
+        ",
+    )
+});
+
+/// Keyspace notification classes [`KeyEvent`]s are built from: keyevent
+/// channels (`E`) of generic commands (`g`), strings (`$`), expiry (`x`) and
+/// eviction (`e`).
+const NOTIFICATION_CLASSES: &str = "Eg$xe";
+
+/// Keyevent channels subscribed to, by the event they report.
+const NOTIFIED_EVENTS: [(&str, KeyEventKind); 6] = [
+    ("set", KeyEventKind::Set),
+    ("incrby", KeyEventKind::Set),
+    ("incrbyfloat", KeyEventKind::Set),
+    ("del", KeyEventKind::Delete),
+    ("expired", KeyEventKind::Expire),
+    ("evicted", KeyEventKind::Evict),
+];
+
+/// Whether Redis refused to increment a value because it is not a number
+/// of the right kind, or because the result would overflow.
+fn is_not_counter(e: &RedisError) -> bool {
+    const REFUSALS: [&str; 4] = [
+        "not an integer",
+        "not a valid float",
+        "would overflow",
+        "NaN or Infinity",
+    ];
+    e.kind() == ErrorKind::ResponseError
+        && e.detail()
+            .is_some_and(|detail| REFUSALS.iter().any(|refusal| detail.contains(refusal)))
+}
+
+/// Set holding the keys tagged with `tag`.
+fn tag_index(tag: &str) -> String {
+    format!("{}{}", TAG_INDEX_PREFIX, tag)
+}
+
+/// Backend storing entries in Redis.
+///
+/// `C` selects the deployment: a single node through [`Client`], or any
+/// other [`Connect`] implementation such as a cluster.
+pub struct RedisBackend<C: Connect = Client> {
+    conn: RedisConnection<C>,
+}
+
+impl<C: Connect> RedisBackend<C> {
+    pub fn new(connector: C, settings: ConnectionSettings) -> Self {
+        Self {
+            conn: RedisConnection::new(connector, settings),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: Connect> CacheBackend for RedisBackend<C> {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        self.conn
+            .run("GET", |mut conn| async move { conn.get(key).await })
+            .await
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        let operation = if ttl.is_some() { "PSETEX" } else { "SET" };
+        self.conn
+            .run(operation, |mut conn| async move {
+                match ttl {
+                    Some(ttl) => conn.pset_ex(key, value, ttl.as_millis() as u64).await,
+                    None => conn.set(key, value).await,
+                }
+            })
+            .await
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        let ttl_ms = ttl.map_or(0, |ttl| ttl.as_millis().max(1) as u64);
+        let existence = match condition {
+            SetCondition::Absent => "NX",
+            SetCondition::Present => "XX",
+            SetCondition::Version(version) => {
+                return self
+                    .conn
+                    .run("EVALSHA", |mut conn| async move {
+                        SET_IF_VERSION
+                            .key(key)
+                            .arg(version)
+                            .arg(value)
+                            .arg(ttl_ms)
+                            .invoke_async(&mut conn)
+                            .await
+                    })
+                    .await;
+            }
+        };
+        let written: Option<String> = self
+            .conn
+            .run("SET", |mut conn| async move {
+                let mut cmd = redis::cmd("SET");
+                cmd.arg(key).arg(value).arg(existence);
+                if ttl_ms > 0 {
+                    cmd.arg("PX").arg(ttl_ms);
+                }
+                cmd.query_async(&mut conn).await
+            })
+            .await?;
+        Ok(written.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        let removed: u64 = self
+            .conn
+            .run("DEL", |mut conn| async move { conn.del(key).await })
+            .await?;
+        Ok(removed > 0)
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        let ms: i64 = self
+            .conn
+            .run("PTTL", |mut conn| async move { conn.pttl(key).await })
+            .await?;
+        Ok(KeyTtl::from_pttl(ms))
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        match ttl {
+            Some(ttl) => {
+                self.conn
+                    .run("PEXPIRE", |mut conn| async move {
+                        conn.pexpire(key, ttl.as_millis() as i64).await
+                    })
+                    .await
+            }
+            // PERSIST alone cannot tell a missing key from one without expiry.
+            None => {
+                let (exists, _): (bool, bool) = self
+                    .conn
+                    .run("PIPELINE", |mut conn| async move {
+                        redis::pipe()
+                            .exists(key)
+                            .persist(key)
+                            .query_async(&mut conn)
+                            .await
+                    })
+                    .await?;
+                Ok(exists)
+            }
+        }
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        // Creating the counter with its expiry first leaves the expiry of an
+        // existing one alone; the script or MULTI keeps other clients from
+        // seeing the zero.
+        let ttl_ms = ttl.map_or(0, |ttl| ttl.as_millis().max(1) as u64);
+        let result = match by {
+            Counter::Integer(by) => self
+                .conn
+                .run("EVALSHA", |mut conn| async move {
+                    INCREMENT_INTEGER
+                        .key(key)
+                        .arg(by)
+                        .arg(ttl_ms)
+                        .invoke_async(&mut conn)
+                        .await
+                })
+                .await
+                .and_then(|(kind, value): (String, String)| {
+                    let counter = match kind.as_str() {
+                        "integer" => value.parse().ok().map(Counter::Integer),
+                        _ => value.parse().ok().map(Counter::Float),
+                    };
+                    counter.ok_or_else(|| BackendError::NotCounter(key.to_string()))
+                }),
+            Counter::Float(by) => {
+                let mut pipe = redis::pipe();
+                pipe.atomic();
+                let create = pipe.cmd("SET").arg(key).arg(0).arg("NX");
+                if ttl_ms > 0 {
+                    create.arg("PX").arg(ttl_ms);
+                }
+                create.ignore();
+                pipe.cmd("INCRBYFLOAT").arg(key).arg(by);
+                self.conn
+                    .run("INCRBYFLOAT", |mut conn| async move {
+                        pipe.query_async(&mut conn).await
+                    })
+                    .await
+                    .map(|(value,): (f64,)| Counter::Float(value))
+            }
+        };
+        result.map_err(|e| match e {
+            BackendError::Redis(e) if is_not_counter(&e) => {
+                BackendError::NotCounter(key.to_string())
+            }
+            e => e,
+        })
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        let (value, ms): (Option<Vec<u8>>, i64) = self
+            .conn
+            .run("PIPELINE", |mut conn| async move {
+                redis::pipe()
+                    .get(key)
+                    .pttl(key)
+                    .query_async(&mut conn)
+                    .await
+            })
+            .await?;
+        Ok(value.map(|value| (value, KeyTtl::from_pttl(ms))))
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let groups = self.conn.connector().pipeline_groups(keys);
+        let replies = try_join_all(groups.into_iter().map(|group| async move {
+            let replies: Vec<(Option<Vec<u8>>, i64)> = self
+                .conn
+                .run("PIPELINE", |mut conn| {
+                    let mut pipe = redis::pipe();
+                    for &index in &group {
+                        pipe.get(keys[index]).pttl(keys[index]);
+                    }
+                    async move { pipe.query_async(&mut conn).await }
+                })
+                .await?;
+            let found = replies
+                .into_iter()
+                .map(|(value, ms)| value.map(|value| (value, KeyTtl::from_pttl(ms))))
+                .collect();
+            BackendResult::Ok((group, found))
+        }))
+        .await?;
+        Ok(scatter(keys.len(), replies))
+    }
+
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+        let keys: Vec<&str> = items.iter().map(|item| item.key).collect();
+        let groups = self.conn.connector().pipeline_groups(&keys);
+        let replies = future::join_all(groups.into_iter().map(|group| async move {
+            let mut pipe = redis::pipe();
+            for &index in &group {
+                let item = &items[index];
+                match item.ttl {
+                    Some(ttl) => pipe.pset_ex(item.key, item.value, ttl.as_millis() as u64),
+                    None => pipe.set(item.key, item.value),
+                }
+                .ignore();
+            }
+            let pipelined = self
+                .conn
+                .run("PIPELINE", |mut conn| async move {
+                    pipe.query_async::<_, ()>(&mut conn).await
+                })
+                .await;
+            let results = match pipelined {
+                Ok(()) => group.iter().map(|_| Ok(())).collect(),
+                Err(e) => {
+                    tracing::debug!("Pipeline of {} writes failed: {}", group.len(), e);
+                    write_each(self, &group, items).await
+                }
+            };
+            (group, results)
+        }))
+        .await;
+        Ok(scatter(items.len(), replies))
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        self.conn
+            .run("DBSIZE", |mut conn| async move {
+                redis::cmd("DBSIZE").query_async(&mut conn).await
+            })
+            .await
+    }
+
+    /// Never uses `KEYS`, which blocks Redis for as long as it walks the
+    /// whole keyspace.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let start = match cursor {
+            Some(cursor) => ScanCursor::parse(cursor)
+                .ok_or_else(|| BackendError::InvalidCursor(cursor.to_string()))?,
+            None => ScanCursor::default(),
+        };
+        let (keys, next) = self
+            .conn
+            .run("SCAN", |conn| {
+                self.conn.connector().scan(conn, start, pattern, count)
+            })
+            .await?;
+        Ok(ScanPage {
+            keys,
+            cursor: next.map(|next| next.to_string()),
+        })
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let groups = self.conn.connector().pipeline_groups(keys);
+        let replies = try_join_all(groups.into_iter().map(|group| async move {
+            let replies: Vec<(usize, i64)> = self
+                .conn
+                .run("PIPELINE", |mut conn| {
+                    let mut pipe = redis::pipe();
+                    for &index in &group {
+                        pipe.strlen(keys[index]).pttl(keys[index]);
+                    }
+                    async move { pipe.query_async(&mut conn).await }
+                })
+                .await?;
+            let found = replies
+                .into_iter()
+                .map(|(size, ms)| match KeyTtl::from_pttl(ms) {
+                    KeyTtl::Missing => None,
+                    ttl => Some(KeyInfo { ttl, size }),
+                })
+                .collect();
+            BackendResult::Ok((group, found))
+        }))
+        .await?;
+        Ok(scatter(keys.len(), replies))
+    }
+
+    /// Tags are indexed in sets without expiry, since the TTL of their keys
+    /// can be changed after they are tagged.
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        try_join_all(tags.iter().map(|tag| {
+            self.conn.run("SADD", move |mut conn| async move {
+                conn.sadd::<_, _, ()>(tag_index(tag), key).await
+            })
+        }))
+        .await?;
+        Ok(())
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let position: u64 = match cursor {
+            Some(cursor) => cursor
+                .parse()
+                .map_err(|_| BackendError::InvalidCursor(cursor.to_string()))?,
+            None => 0,
+        };
+        let (next, keys): (u64, Vec<String>) = self
+            .conn
+            .run("SSCAN", |mut conn| async move {
+                redis::cmd("SSCAN")
+                    .arg(tag_index(tag))
+                    .arg(position)
+                    .arg("COUNT")
+                    .arg(count)
+                    .query_async(&mut conn)
+                    .await
+            })
+            .await?;
+        Ok(ScanPage {
+            keys,
+            cursor: (next != 0).then(|| next.to_string()),
+        })
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        self.conn
+            .run("SREM", |mut conn| async move {
+                conn.srem(tag_index(tag), keys).await
+            })
+            .await
+    }
+
+    /// Merges the keyevent notifications of every node the connector names
+    /// as an event source.
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        let sources = self
+            .conn
+            .run("event_sources", |conn| {
+                self.conn.connector().event_sources(conn)
+            })
+            .await?;
+        let settings = self.conn.settings();
+        let timeout = settings.command_timeout;
+        let mut streams = Vec::with_capacity(sources.len());
+        for client in sources {
+            let subscription = subscribe(client, settings.configure_notifications);
+            let stream = tokio::time::timeout(timeout, subscription)
+                .await
+                .map_err(|_| BackendError::Timeout(timeout))??;
+            streams.push(stream);
+        }
+        Ok(merge_events(streams))
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        self.conn.health().await
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        serde_json::json!({ "connection": self.conn.stats() })
+    }
+}
+
+/// Subscribes to the keyevent notifications of one node.
+///
+/// With `configure`, notification classes that are off are turned on.
+async fn subscribe(client: Client, configure: bool) -> RedisResult<KeyEvents> {
+    let address = client.get_connection_info().addr.to_string();
+    let db = client.get_connection_info().redis.db;
+    let mut conn = client.get_async_connection().await?;
+    match check_notifications(&mut conn, configure).await {
+        Ok(missing) if missing.is_empty() => {}
+        Ok(missing) => tracing::warn!(
+            "Keyspace notification classes {:?} are off on {}, so their events are not \
+             reported; notify-keyspace-events must include {}",
+            missing,
+            address,
+            NOTIFICATION_CLASSES
+        ),
+        Err(e) => tracing::warn!(
+            "Cannot check keyspace notifications on {}, notify-keyspace-events must include {}: {}",
+            address,
+            NOTIFICATION_CLASSES,
+            e
+        ),
+    }
+    let mut pubsub = conn.into_pubsub();
+    for (event, _) in NOTIFIED_EVENTS {
+        pubsub
+            .subscribe(format!("__keyevent@{}__:{}", db, event))
+            .await?;
+    }
+    tracing::info!("Watching keyspace notifications of {}", address);
+    Ok(pubsub
+        .into_on_message()
+        .filter_map(|msg| future::ready(key_event(&msg)))
+        .boxed())
+}
+
+/// Returns the notification classes events need that are off, after turning
+/// them on with `configure`.
+async fn check_notifications(
+    conn: &mut redis::aio::Connection,
+    configure: bool,
+) -> RedisResult<String> {
+    let (_, current): (String, String) = redis::cmd("CONFIG")
+        .arg("GET")
+        .arg("notify-keyspace-events")
+        .query_async(conn)
+        .await?;
+    // `A` stands for every class but the channel kinds `K` and `E`.
+    let enabled = |class: char| current.contains(class) || (class != 'E' && current.contains('A'));
+    let missing: String = NOTIFICATION_CLASSES
+        .chars()
+        .filter(|&class| !enabled(class))
+        .collect();
+    if missing.is_empty() || !configure {
+        return Ok(missing);
+    }
+    redis::cmd("CONFIG")
+        .arg("SET")
+        .arg("notify-keyspace-events")
+        .arg(format!("{}{}", current, missing))
+        .query_async::<_, ()>(conn)
+        .await?;
+    Ok(String::new())
+}
+
+/// Interprets a message on a `__keyevent@<db>__:<event>` channel.
+fn key_event(msg: &Msg) -> Option<KeyEvent> {
+    let (_, event) = msg.get_channel_name().rsplit_once(':')?;
+    let (_, kind) = NOTIFIED_EVENTS.iter().find(|(name, _)| *name == event)?;
+    Some(KeyEvent {
+        kind: *kind,
+        key: msg.get_payload().ok()?,
+    })
+}
@@ -0,0 +1,135 @@
+use std::collections::BTreeMap;
+
+/// Consistent-hash ring mapping keys to nodes.
+///
+/// Each node is placed on the ring at `virtual_nodes` points derived from
+/// its name, so adding or removing a node only moves the keys between its
+/// points and their predecessors. Hashing is stable across processes, so
+/// every replica of the service routes a key to the same node.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    nodes: Vec<String>,
+    virtual_nodes: usize,
+    points: BTreeMap<u64, usize>,
+}
+
+impl HashRing {
+    pub fn new(nodes: Vec<String>, virtual_nodes: usize) -> Self {
+        let mut points = BTreeMap::new();
+        for (index, node) in nodes.iter().enumerate() {
+            for vnode in 0..virtual_nodes {
+                points.insert(hash(format!("{}#{}", node, vnode).as_bytes()), index);
+            }
+        }
+        Self {
+            nodes,
+            virtual_nodes,
+            points,
+        }
+    }
+
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    pub fn virtual_nodes(&self) -> usize {
+        self.virtual_nodes
+    }
+
+    /// Index of the node owning `key`, or `None` if the ring is empty.
+    pub fn node_for(&self, key: &str) -> Option<usize> {
+        let point = hash(key.as_bytes());
+        self.points
+            .range(point..)
+            .next()
+            .or_else(|| self.points.iter().next())
+            .map(|(_, &node)| node)
+    }
+
+    /// Fraction of the hash space owned by each node.
+    pub fn ownership(&self) -> Vec<f64> {
+        let mut owned = vec![0u128; self.nodes.len()];
+        let Some((&last, _)) = self.points.iter().next_back() else {
+            return vec![0.0; self.nodes.len()];
+        };
+        // Each point owns the arc from its predecessor (exclusive) to itself.
+        let mut previous = last;
+        for (&point, &node) in &self.points {
+            owned[node] += point.wrapping_sub(previous) as u128;
+            previous = point;
+        }
+        if self.points.len() == 1 {
+            return vec![1.0];
+        }
+        let total = u64::MAX as f64 + 1.0;
+        owned.iter().map(|&arc| arc as f64 / total).collect()
+    }
+}
+
+/// 64-bit FNV-1a followed by the MurmurHash3 finalizer to spread
+/// similar keys across the ring.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        h ^= byte as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51afd7ed558ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+    h ^ (h >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(nodes: &[&str]) -> HashRing {
+        HashRing::new(nodes.iter().map(|n| n.to_string()).collect(), 160)
+    }
+
+    #[test]
+    fn routing_is_deterministic() {
+        let a = ring(&["redis-a:6379", "redis-b:6379", "redis-c:6379"]);
+        let b = ring(&["redis-a:6379", "redis-b:6379", "redis-c:6379"]);
+        for i in 0..100 {
+            let key = format!("user:{}", i);
+            assert_eq!(a.node_for(&key), b.node_for(&key));
+        }
+        assert_eq!(HashRing::new(Vec::new(), 160).node_for("key"), None);
+    }
+
+    #[test]
+    fn adding_a_node_moves_a_small_share_of_keys() {
+        let before = ring(&["a", "b", "c", "d"]);
+        let after = ring(&["a", "b", "c", "d", "e"]);
+        let keys = 10_000;
+        let moved = (0..keys)
+            .map(|i| format!("key:{}", i))
+            .filter(|key| {
+                let (old, new) = (before.node_for(key), after.node_for(key));
+                old != new && new != Some(4)
+            })
+            .count();
+        assert_eq!(moved, 0, "keys may only move to the new node");
+
+        let to_new = (0..keys)
+            .filter(|i| after.node_for(&format!("key:{}", i)) == Some(4))
+            .count();
+        assert!(
+            to_new > keys / 10 && to_new < keys * 3 / 10,
+            "moved {}",
+            to_new
+        );
+    }
+
+    #[test]
+    fn ownership_is_balanced_and_sums_to_one() {
+        let shares = ring(&["a", "b", "c"]).ownership();
+        assert!((shares.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        for share in shares {
+            assert!(share > 0.25 && share < 0.42, "share {}", share);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use super::{Connect, ConnectionSettings, RedisBackend};
+use async_trait::async_trait;
+use redis::aio::{ConnectionLike, MultiplexedConnection};
+use redis::sentinel::{Sentinel, SentinelNodeConnectionInfo};
+use redis::{Cmd, ErrorKind, Pipeline, RedisError, RedisFuture, RedisResult, Value};
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+
+/// Backend storing entries in the primary of a Sentinel-managed deployment.
+pub type SentinelBackend = RedisBackend<SentinelConnector>;
+
+impl SentinelBackend {
+    /// Creates a backend resolving the primary of `service_name` through `sentinels`,
+    /// re-checking it every `check_interval`.
+    pub fn sentinel(
+        sentinels: &[String],
+        service_name: &str,
+        check_interval: Duration,
+        settings: ConnectionSettings,
+    ) -> RedisResult<Self> {
+        let connector = SentinelConnector {
+            sentinels: sentinels.to_vec(),
+            service_name: service_name.to_string(),
+            check_interval,
+        };
+        // Validate the sentinel addresses up front rather than on first use.
+        Sentinel::build(connector.sentinels.clone())?;
+        Ok(Self::new(connector, settings))
+    }
+}
+
+pub struct SentinelConnector {
+    sentinels: Vec<String>,
+    service_name: String,
+    check_interval: Duration,
+}
+
+#[async_trait]
+impl Connect for SentinelConnector {
+    type Connection = SentinelConnection;
+
+    fn name(&self) -> String {
+        format!("sentinel/{}", self.service_name)
+    }
+
+    async fn connect(&self, _settings: &ConnectionSettings) -> RedisResult<SentinelConnection> {
+        let shared = Arc::new(Shared {
+            sentinel: Mutex::new(Sentinel::build(self.sentinels.clone())?),
+            service_name: self.service_name.clone(),
+            primary: RwLock::new(None),
+            generation: AtomicU64::new(0),
+            failovers: AtomicU64::new(0),
+            last_address: std::sync::Mutex::new(None),
+        });
+        shared.primary().await?;
+        tokio::spawn(watch_primary(Arc::downgrade(&shared), self.check_interval));
+        Ok(SentinelConnection { shared })
+    }
+
+    /// The current primary; subscriptions are re-established on it after a
+    /// failover drops them.
+    async fn event_sources(&self, conn: SentinelConnection) -> RedisResult<Vec<redis::Client>> {
+        Ok(vec![conn.shared.resolve().await?])
+    }
+
+    async fn health(&self, mut conn: SentinelConnection) -> RedisResult<serde_json::Value> {
+        redis::cmd("PING").query_async::<_, ()>(&mut conn).await?;
+        Ok(json!({
+            "service": self.service_name,
+            "sentinels": self.sentinels.len(),
+            "primary": *conn.shared.last_address.lock().unwrap(),
+            "failovers": conn.shared.failovers.load(Ordering::Relaxed),
+        }))
+    }
+}
+
+struct Primary {
+    address: String,
+    conn: MultiplexedConnection,
+    generation: u64,
+}
+
+struct Shared {
+    sentinel: Mutex<Sentinel>,
+    service_name: String,
+    primary: RwLock<Option<Primary>>,
+    generation: AtomicU64,
+    failovers: AtomicU64,
+    last_address: std::sync::Mutex<Option<String>>,
+}
+
+impl Shared {
+    /// Asks the sentinels for the current primary's address.
+    async fn resolve(&self) -> RedisResult<redis::Client> {
+        let node_info = SentinelNodeConnectionInfo::default();
+        self.sentinel
+            .lock()
+            .await
+            .async_master_for(&self.service_name, Some(&node_info))
+            .await
+    }
+
+    /// Returns a connection to the primary, resolving it first if needed.
+    async fn primary(&self) -> RedisResult<(MultiplexedConnection, u64)> {
+        if let Some(primary) = &*self.primary.read().await {
+            return Ok((primary.conn.clone(), primary.generation));
+        }
+
+        let mut slot = self.primary.write().await;
+        if let Some(primary) = &*slot {
+            return Ok((primary.conn.clone(), primary.generation));
+        }
+        let client = self.resolve().await?;
+        let address = client.get_connection_info().addr.to_string();
+        let conn = client.get_multiplexed_tokio_connection().await?;
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+
+        let previous = self.last_address.lock().unwrap().replace(address.clone());
+        match previous {
+            Some(previous) if previous != address => {
+                self.failovers.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!("Redis primary moved from {} to {}", previous, address);
+            }
+            Some(_) => tracing::info!("Reconnected to Redis primary {}", address),
+            None => tracing::info!("Resolved Redis primary {}", address),
+        }
+
+        *slot = Some(Primary {
+            address,
+            conn: conn.clone(),
+            generation,
+        });
+        Ok((conn, generation))
+    }
+
+    /// Drops the connection of `generation` so the next command re-resolves the primary.
+    async fn invalidate(&self, generation: u64) {
+        let mut slot = self.primary.write().await;
+        if slot
+            .as_ref()
+            .is_some_and(|primary| primary.generation == generation)
+        {
+            *slot = None;
+        }
+    }
+
+    /// Re-resolves the primary and drops the connection if it has moved.
+    async fn verify(&self) {
+        let current = match &*self.primary.read().await {
+            Some(primary) => (primary.address.clone(), primary.generation),
+            None => return,
+        };
+        match self.resolve().await {
+            Ok(client) if client.get_connection_info().addr.to_string() != current.0 => {
+                self.invalidate(current.1).await;
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to query sentinels: {}", e),
+        }
+    }
+}
+
+async fn watch_primary(shared: Weak<Shared>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        shared.verify().await;
+    }
+}
+
+/// Errors after which the cached primary can no longer be trusted.
+fn is_failover(e: &RedisError) -> bool {
+    e.kind() == ErrorKind::ReadOnly
+        || e.is_io_error()
+        || e.is_connection_dropped()
+        || e.is_connection_refusal()
+}
+
+/// Errors guaranteeing the command was not applied, so it is safe to retry.
+fn is_retryable(e: &RedisError) -> bool {
+    e.kind() == ErrorKind::ReadOnly || e.is_connection_refusal()
+}
+
+/// Connection to whichever node the sentinels currently report as primary.
+///
+/// Connection loss and `READONLY` replies (the old primary was demoted)
+/// drop the connection; commands that were certainly not applied are
+/// retried once against the newly resolved primary.
+#[derive(Clone)]
+pub struct SentinelConnection {
+    shared: Arc<Shared>,
+}
+
+impl SentinelConnection {
+    async fn on_error(&self, e: &RedisError, generation: u64) -> bool {
+        if !is_failover(e) {
+            return false;
+        }
+        self.shared.invalidate(generation).await;
+        is_retryable(e)
+    }
+}
+
+impl ConnectionLike for SentinelConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            let (mut conn, generation) = self.shared.primary().await?;
+            match conn.req_packed_command(cmd).await {
+                Err(e) if self.on_error(&e, generation).await => {
+                    let (mut conn, _) = self.shared.primary().await?;
+                    conn.req_packed_command(cmd).await
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            let (mut conn, generation) = self.shared.primary().await?;
+            match conn.req_packed_commands(cmd, offset, count).await {
+                Err(e) if self.on_error(&e, generation).await => {
+                    let (mut conn, _) = self.shared.primary().await?;
+                    conn.req_packed_commands(cmd, offset, count).await
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        0
+    }
+}
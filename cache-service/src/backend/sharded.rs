@@ -0,0 +1,391 @@
+use super::{
+    merge_events, scatter, write_each, BackendError, BackendResult, CacheBackend, Counter,
+    HashRing, Hit, KeyEvents, KeyInfo, KeyTtl, ScanPage, SetCondition, SetItem,
+};
+use async_trait::async_trait;
+use futures::future::{self, try_join_all};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Backend spreading keys over independent instances with a consistent-hash ring.
+pub struct ShardedBackend {
+    ring: HashRing,
+    shards: Vec<Arc<dyn CacheBackend>>,
+}
+
+impl ShardedBackend {
+    /// Creates a backend over named shards. Names place the shards on the
+    /// ring, so they must stay stable for keys to keep their placement.
+    pub fn new(shards: Vec<(String, Arc<dyn CacheBackend>)>, virtual_nodes: usize) -> Self {
+        let (names, shards) = shards.into_iter().unzip();
+        Self {
+            ring: HashRing::new(names, virtual_nodes),
+            shards,
+        }
+    }
+
+    fn shard(&self, key: &str) -> BackendResult<&dyn CacheBackend> {
+        Ok(self.shards[self.shard_index(key)?].as_ref())
+    }
+
+    fn shard_index(&self, key: &str) -> BackendResult<usize> {
+        self.ring.node_for(key).ok_or(BackendError::EmptyRing)
+    }
+
+    /// Splits a batch of keys by owning shard, as indices into `keys`.
+    fn partition<'a>(
+        &self,
+        keys: impl Iterator<Item = &'a str>,
+    ) -> BackendResult<Vec<(usize, Vec<usize>)>> {
+        let mut groups = vec![Vec::new(); self.shards.len()];
+        for (index, key) in keys.enumerate() {
+            groups[self.shard_index(key)?].push(index);
+        }
+        Ok(groups
+            .into_iter()
+            .enumerate()
+            .filter(|(_, group)| !group.is_empty())
+            .collect())
+    }
+
+    /// Splits a cursor of the form `<shard>:<cursor of the shard>` as used
+    /// when iterating over the shards one after the other.
+    fn resume<'a>(&self, cursor: Option<&'a str>) -> BackendResult<(usize, Option<&'a str>)> {
+        let Some(cursor) = cursor else {
+            return Ok((0, None));
+        };
+        let invalid = || BackendError::InvalidCursor(cursor.to_string());
+        let (shard, shard_cursor) = cursor.split_once(':').ok_or_else(invalid)?;
+        let shard: usize = shard.parse().map_err(|_| invalid())?;
+        if shard >= self.shards.len() {
+            return Err(invalid());
+        }
+        // Empty when the shard has not been started yet.
+        Ok((shard, Some(shard_cursor).filter(|c| !c.is_empty())))
+    }
+
+    /// Turns a page of `shard` into one of the whole iteration, moving on to
+    /// the next shard once this one is done.
+    fn continued(&self, shard: usize, page: ScanPage) -> ScanPage {
+        let cursor = match page.cursor {
+            Some(next) => Some(format!("{}:{}", shard, next)),
+            None if shard + 1 < self.shards.len() => Some(format!("{}:", shard + 1)),
+            None => None,
+        };
+        ScanPage {
+            keys: page.keys,
+            cursor,
+        }
+    }
+
+    /// Ring layout with each shard's share of the hash space and key count.
+    pub async fn ring_report(&self) -> serde_json::Value {
+        let ownership = self.ring.ownership();
+        let mut shards = Vec::with_capacity(self.shards.len());
+        for (index, name) in self.ring.nodes().iter().enumerate() {
+            let mut shard = json!({
+                "name": name,
+                "virtual_nodes": self.ring.virtual_nodes(),
+                "ownership": ownership[index],
+            });
+            match self.shards[index].key_count().await {
+                Ok(keys) => shard["keys"] = keys.into(),
+                Err(e) => shard["error"] = e.to_string().into(),
+            }
+            shards.push(shard);
+        }
+        json!({ "shards": shards })
+    }
+}
+
+#[async_trait]
+impl CacheBackend for ShardedBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        self.shard(key)?.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        self.shard(key)?.set(key, value, ttl).await
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        self.shard(key)?.set_if(key, value, ttl, condition).await
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        self.shard(key)?.delete(key).await
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        self.shard(key)?.ttl(key).await
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        self.shard(key)?.set_ttl(key, ttl).await
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        self.shard(key)?.increment(key, by, ttl).await
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        self.shard(key)?.get_with_ttl(key).await
+    }
+
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        self.shard(key)?.lookup(key).await
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        let groups = self.partition(keys.iter().copied())?;
+        let replies = try_join_all(groups.into_iter().map(|(shard, group)| async move {
+            let shard_keys: Vec<&str> = group.iter().map(|&index| keys[index]).collect();
+            let found = self.shards[shard].get_many_with_ttl(&shard_keys).await?;
+            BackendResult::Ok((group, found))
+        }))
+        .await?;
+        Ok(scatter(keys.len(), replies))
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        let groups = self.partition(keys.iter().copied())?;
+        let replies = try_join_all(groups.into_iter().map(|(shard, group)| async move {
+            let shard_keys: Vec<&str> = group.iter().map(|&index| keys[index]).collect();
+            let found = self.shards[shard].inspect_many(&shard_keys).await?;
+            BackendResult::Ok((group, found))
+        }))
+        .await?;
+        Ok(scatter(keys.len(), replies))
+    }
+
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        let groups = self.partition(items.iter().map(|item| item.key))?;
+        // A shard failing as a whole only fails its own items, which are
+        // then written one by one to learn their outcome.
+        let replies = future::join_all(groups.into_iter().map(|(shard, group)| async move {
+            let shard_items: Vec<SetItem> = group.iter().map(|&index| items[index]).collect();
+            let results = match self.shards[shard].set_many(&shard_items).await {
+                Ok(results) => results,
+                Err(_) => write_each(self.shards[shard].as_ref(), &group, items).await,
+            };
+            (group, results)
+        }))
+        .await;
+        Ok(scatter(items.len(), replies))
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        let mut total = 0;
+        for shard in &self.shards {
+            total += shard.key_count().await?;
+        }
+        Ok(total)
+    }
+
+    /// Scans the shards one after the other, with cursors of the form
+    /// `<shard>:<cursor of the shard>`.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let (shard, shard_cursor) = self.resume(cursor)?;
+        let page = self.shards[shard]
+            .scan(shard_cursor, pattern, count)
+            .await?;
+        Ok(self.continued(shard, page))
+    }
+
+    /// Tags are indexed on the shard of each key, so each shard's index is
+    /// listed in turn like a scan.
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        self.shard(key)?.tag(key, tags).await
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        let (shard, shard_cursor) = self.resume(cursor)?;
+        let page = self.shards[shard].tagged(tag, shard_cursor, count).await?;
+        Ok(self.continued(shard, page))
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        let groups = self.partition(keys.iter().copied())?;
+        try_join_all(groups.into_iter().map(|(shard, group)| async move {
+            let shard_keys: Vec<&str> = group.iter().map(|&index| keys[index]).collect();
+            self.shards[shard].untag(tag, &shard_keys).await
+        }))
+        .await?;
+        Ok(())
+    }
+
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        let streams = try_join_all(self.shards.iter().map(|shard| shard.watch())).await?;
+        Ok(merge_events(streams))
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        let mut shards = serde_json::Map::new();
+        for (name, shard) in self.ring.nodes().iter().zip(&self.shards) {
+            shards.insert(name.clone(), shard.health().await);
+        }
+        json!({ "shards": shards })
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        let shards: serde_json::Map<_, _> = self
+            .ring
+            .nodes()
+            .iter()
+            .zip(&self.shards)
+            .map(|(name, shard)| (name.clone(), shard.stats()))
+            .collect();
+        json!({ "shards": shards })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, MemoryLimits};
+
+    #[tokio::test]
+    async fn batches_are_split_by_shard_and_keep_their_order() {
+        let limits = MemoryLimits {
+            max_entries: 100,
+            max_bytes: 4096,
+        };
+        let shards: Vec<Arc<MemoryBackend>> = (0..3)
+            .map(|_| Arc::new(MemoryBackend::new(limits)))
+            .collect();
+        let cache = ShardedBackend::new(
+            shards
+                .iter()
+                .enumerate()
+                .map(|(i, shard)| {
+                    (
+                        format!("shard-{}", i),
+                        shard.clone() as Arc<dyn CacheBackend>,
+                    )
+                })
+                .collect(),
+            160,
+        );
+
+        let keys: Vec<String> = (0..20).map(|i| format!("key:{}", i)).collect();
+        let items: Vec<SetItem> = keys
+            .iter()
+            .map(|key| SetItem {
+                key,
+                value: key.as_bytes(),
+                ttl: None,
+            })
+            .collect();
+        let results = cache.set_many(&items).await.unwrap();
+        assert!(results.iter().all(Result::is_ok));
+        assert!(shards.iter().all(|shard| !shard.is_empty()));
+
+        let mut lookup: Vec<&str> = keys.iter().map(String::as_str).collect();
+        lookup.push("absent");
+        let found = cache.get_many_with_ttl(&lookup).await.unwrap();
+        for (key, entry) in lookup.iter().zip(&found) {
+            let expected = (*key != "absent").then(|| key.as_bytes().to_vec());
+            assert_eq!(entry.as_ref().map(|(value, _)| value.clone()), expected);
+        }
+
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = cache.scan(cursor.as_deref(), "key:*", 4).await.unwrap();
+            listed.extend(page.keys);
+            cursor = match page.cursor {
+                Some(next) => Some(next),
+                None => break,
+            };
+        }
+        listed.sort();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(listed, expected);
+        assert!(matches!(
+            cache.scan(Some("7:"), "*", 4).await,
+            Err(BackendError::InvalidCursor(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_failing_shard_only_fails_its_own_items() {
+        use crate::backend::BreakerBackend;
+        use crate::circuit_breaker::{BreakerSettings, CircuitBreaker};
+
+        let memory = |_| {
+            Arc::new(MemoryBackend::new(MemoryLimits {
+                max_entries: 100,
+                max_bytes: 4096,
+            })) as Arc<dyn CacheBackend>
+        };
+        let breaker = Arc::new(CircuitBreaker::new(
+            "down",
+            BreakerSettings {
+                failure_threshold: 1,
+                reset_timeout: Duration::from_secs(60),
+                half_open_max_requests: 1,
+            },
+        ));
+        breaker.try_acquire().unwrap().record(false);
+        let down: Arc<dyn CacheBackend> = Arc::new(BreakerBackend::new(memory(0), breaker));
+        let cache = ShardedBackend::new(vec![("up".into(), memory(1)), ("down".into(), down)], 160);
+
+        let keys: Vec<String> = (0..20).map(|i| format!("key:{}", i)).collect();
+        let items: Vec<SetItem> = keys
+            .iter()
+            .map(|key| SetItem {
+                key,
+                value: b"1",
+                ttl: None,
+            })
+            .collect();
+        let results = cache.set_many(&items).await.unwrap();
+        for (key, result) in keys.iter().zip(&results) {
+            match cache.shard_index(key).unwrap() {
+                0 => assert!(result.is_ok()),
+                _ => assert!(matches!(result, Err(BackendError::CircuitOpen { .. }))),
+            }
+        }
+        assert!(results.iter().any(Result::is_ok) && results.iter().any(Result::is_err));
+    }
+
+    #[tokio::test]
+    async fn an_empty_ring_fails_requests_instead_of_panicking() {
+        let shard: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let cache = ShardedBackend::new(vec![("only".into(), shard)], 0);
+        assert!(matches!(cache.get("a").await, Err(BackendError::EmptyRing)));
+        assert!(matches!(
+            cache.get_many_with_ttl(&["a", "b"]).await,
+            Err(BackendError::EmptyRing)
+        ));
+    }
+}
@@ -0,0 +1,439 @@
+use super::{
+    BackendResult, CacheBackend, Counter, Hit, KeyEvents, KeyInfo, KeyTtl, MemoryBackend,
+    MemoryLimits, ScanPage, SetCondition, SetItem,
+};
+use crate::metrics;
+use async_trait::async_trait;
+use serde_json::json;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Number of write generation counters keys are spread over.
+const GENERATION_STRIPES: usize = 1024;
+
+struct TierCounters {
+    tier: &'static str,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl TierCounters {
+    fn new(tier: &'static str) -> Self {
+        Self {
+            tier,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, hit: bool) {
+        let (counter, result) = if hit {
+            (&self.hits, "hit")
+        } else {
+            (&self.misses, "miss")
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        metrics::TIER_LOOKUPS_TOTAL
+            .with_label_values(&[self.tier, result])
+            .inc();
+    }
+
+    fn snapshot(&self) -> serde_json::Value {
+        json!({
+            "hits": self.hits.load(Ordering::Relaxed),
+            "misses": self.misses.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Prepends to an L1 copy the lifetime of its L2 key, so that L1 hits report
+/// the L2 lifetime rather than the capped one of the copy: `0` for keys that
+/// never expire, else `1` and the expiry in Unix milliseconds.
+fn encode_l1(value: &[u8], ttl: KeyTtl) -> Vec<u8> {
+    let mut stored = Vec::with_capacity(9 + value.len());
+    match ttl {
+        KeyTtl::Expires(remaining) => match SystemTime::now().checked_add(remaining) {
+            Some(expires_at) => {
+                let expires_at = expires_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                stored.push(1);
+                stored.extend_from_slice(&(expires_at.as_millis() as u64).to_be_bytes());
+            }
+            None => stored.push(0),
+        },
+        _ => stored.push(0),
+    }
+    stored.extend_from_slice(value);
+    stored
+}
+
+/// Splits an L1 copy into the value and the lifetime of its L2 key.
+fn decode_l1(stored: Vec<u8>) -> (Vec<u8>, KeyTtl) {
+    let Some((&expires, rest)) = stored.split_first() else {
+        return (stored, KeyTtl::Persistent);
+    };
+    match rest.split_first_chunk::<8>() {
+        Some((ms, value)) if expires == 1 => {
+            let expires_at = UNIX_EPOCH + Duration::from_millis(u64::from_be_bytes(*ms));
+            let remaining = expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            (value.to_vec(), KeyTtl::Expires(remaining))
+        }
+        _ => (rest.to_vec(), KeyTtl::Persistent),
+    }
+}
+
+/// Bounded in-process L1 in front of a shared L2 backend.
+///
+/// L1 is filled on L2 hits and never outlives the remaining L2 TTL, capped
+/// at `max_l1_ttl` so writes made by other replicas are picked up. Writes
+/// and deletes go to L2 and drop the L1 copy.
+///
+/// Each write also bumps the generation of its key, shared with the other
+/// keys of its stripe, and readers only fill L1 if the generation did not
+/// change since they read L2, so that a write racing a read is never
+/// shadowed by the value it replaced.
+pub struct TieredBackend {
+    l1: MemoryBackend,
+    l2: Arc<dyn CacheBackend>,
+    max_l1_ttl: Duration,
+    generations: Box<[AtomicU64]>,
+    l1_counters: TierCounters,
+    l2_counters: TierCounters,
+}
+
+impl TieredBackend {
+    pub fn new(l2: Arc<dyn CacheBackend>, l1_limits: MemoryLimits, max_l1_ttl: Duration) -> Self {
+        Self {
+            l1: MemoryBackend::new(l1_limits),
+            l2,
+            max_l1_ttl,
+            generations: (0..GENERATION_STRIPES).map(|_| AtomicU64::new(0)).collect(),
+            l1_counters: TierCounters::new("l1"),
+            l2_counters: TierCounters::new("l2"),
+        }
+    }
+
+    fn generation(&self, key: &str) -> &AtomicU64 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.generations[hasher.finish() as usize % self.generations.len()]
+    }
+
+    /// Drops the L1 copy of a key written to L2.
+    async fn invalidate(&self, key: &str) -> BackendResult<()> {
+        self.generation(key).fetch_add(1, Ordering::AcqRel);
+        self.l1.delete(key).await?;
+        Ok(())
+    }
+
+    /// Copies a value read from L2 into L1, unless the key was written since
+    /// its generation was `generation`.
+    async fn fill_l1(&self, key: &str, value: &[u8], l2_ttl: KeyTtl, generation: u64) {
+        let written = || self.generation(key).load(Ordering::Acquire) != generation;
+        if written() {
+            return;
+        }
+        let ttl = match l2_ttl {
+            KeyTtl::Missing => return,
+            KeyTtl::Persistent => self.max_l1_ttl,
+            KeyTtl::Expires(remaining) => remaining.min(self.max_l1_ttl),
+        };
+        if ttl.is_zero() {
+            return;
+        }
+        let stored = encode_l1(value, l2_ttl);
+        if let Err(e) = self.l1.set(key, &stored, Some(ttl)).await {
+            tracing::debug!("Not caching key {} in L1: {}", key, e);
+        }
+        // A write that dropped L1 just before the copy was made must not be
+        // undone by it.
+        if written() {
+            let _ = self.l1.delete(key).await;
+        }
+    }
+}
+
+#[async_trait]
+impl CacheBackend for TieredBackend {
+    async fn get(&self, key: &str) -> BackendResult<Option<Vec<u8>>> {
+        Ok(self.get_with_ttl(key).await?.map(|(value, _)| value))
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> BackendResult<Option<(Vec<u8>, KeyTtl)>> {
+        Ok(self.lookup(key).await?.map(|hit| (hit.value, hit.ttl)))
+    }
+
+    /// L1 hits are as old as their L1 copy, and report the lifetime of the
+    /// L2 key.
+    async fn lookup(&self, key: &str) -> BackendResult<Option<Hit>> {
+        if let Some(hit) = self.l1.lookup(key).await? {
+            self.l1_counters.record(true);
+            let (value, ttl) = decode_l1(hit.value);
+            return Ok(Some(Hit { value, ttl, ..hit }));
+        }
+        self.l1_counters.record(false);
+
+        let generation = self.generation(key).load(Ordering::Acquire);
+        let found = self.l2.lookup(key).await?;
+        self.l2_counters.record(found.is_some());
+        if let Some(hit) = &found {
+            self.fill_l1(key, &hit.value, hit.ttl, generation).await;
+        }
+        Ok(found)
+    }
+
+    async fn set(&self, key: &str, value: &[u8], ttl: Option<Duration>) -> BackendResult<()> {
+        let result = self.l2.set(key, value, ttl).await;
+        self.invalidate(key).await?;
+        result
+    }
+
+    async fn get_many_with_ttl(
+        &self,
+        keys: &[&str],
+    ) -> BackendResult<Vec<Option<(Vec<u8>, KeyTtl)>>> {
+        let mut found = Vec::with_capacity(keys.len());
+        let mut misses = Vec::new();
+        for (index, key) in keys.iter().enumerate() {
+            let hit = self.l1.get(key).await?.map(decode_l1);
+            self.l1_counters.record(hit.is_some());
+            if hit.is_none() {
+                misses.push(index);
+            }
+            found.push(hit);
+        }
+        if misses.is_empty() {
+            return Ok(found);
+        }
+
+        let miss_keys: Vec<&str> = misses.iter().map(|&index| keys[index]).collect();
+        let generations: Vec<u64> = miss_keys
+            .iter()
+            .map(|key| self.generation(key).load(Ordering::Acquire))
+            .collect();
+        let l2 = self.l2.get_many_with_ttl(&miss_keys).await?;
+        for ((index, entry), generation) in misses.into_iter().zip(l2).zip(generations) {
+            self.l2_counters.record(entry.is_some());
+            if let Some((value, ttl)) = &entry {
+                self.fill_l1(keys[index], value, *ttl, generation).await;
+            }
+            found[index] = entry;
+        }
+        Ok(found)
+    }
+
+    async fn set_many(&self, items: &[SetItem<'_>]) -> BackendResult<Vec<BackendResult<()>>> {
+        let results = self.l2.set_many(items).await;
+        for item in items {
+            self.invalidate(item.key).await?;
+        }
+        results
+    }
+
+    async fn set_if(
+        &self,
+        key: &str,
+        value: &[u8],
+        ttl: Option<Duration>,
+        condition: &SetCondition,
+    ) -> BackendResult<bool> {
+        let result = self.l2.set_if(key, value, ttl, condition).await;
+        self.invalidate(key).await?;
+        result
+    }
+
+    async fn delete(&self, key: &str) -> BackendResult<bool> {
+        let result = self.l2.delete(key).await;
+        self.invalidate(key).await?;
+        result
+    }
+
+    async fn ttl(&self, key: &str) -> BackendResult<KeyTtl> {
+        self.l2.ttl(key).await
+    }
+
+    async fn set_ttl(&self, key: &str, ttl: Option<Duration>) -> BackendResult<bool> {
+        let result = self.l2.set_ttl(key, ttl).await;
+        self.invalidate(key).await?;
+        result
+    }
+
+    async fn increment(
+        &self,
+        key: &str,
+        by: Counter,
+        ttl: Option<Duration>,
+    ) -> BackendResult<Counter> {
+        let result = self.l2.increment(key, by, ttl).await;
+        self.invalidate(key).await?;
+        result
+    }
+
+    async fn key_count(&self) -> BackendResult<u64> {
+        self.l2.key_count().await
+    }
+
+    /// Lists the keys of L2, which holds every key of L1.
+    async fn scan(
+        &self,
+        cursor: Option<&str>,
+        pattern: &str,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.l2.scan(cursor, pattern, count).await
+    }
+
+    async fn tag(&self, key: &str, tags: &[&str]) -> BackendResult<()> {
+        self.l2.tag(key, tags).await
+    }
+
+    async fn tagged(
+        &self,
+        tag: &str,
+        cursor: Option<&str>,
+        count: usize,
+    ) -> BackendResult<ScanPage> {
+        self.l2.tagged(tag, cursor, count).await
+    }
+
+    async fn untag(&self, tag: &str, keys: &[&str]) -> BackendResult<()> {
+        self.l2.untag(tag, keys).await
+    }
+
+    async fn watch(&self) -> BackendResult<KeyEvents> {
+        self.l2.watch().await
+    }
+
+    async fn inspect_many(&self, keys: &[&str]) -> BackendResult<Vec<Option<KeyInfo>>> {
+        self.l2.inspect_many(keys).await
+    }
+
+    async fn health(&self) -> serde_json::Value {
+        self.l2.health().await
+    }
+
+    fn stats(&self) -> serde_json::Value {
+        let mut l1 = self.l1_counters.snapshot();
+        l1["entries"] = self.l1.len().into();
+        json!({
+            "l1": l1,
+            "l2": self.l2_counters.snapshot(),
+            "l2_backend": self.l2.stats(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiered() -> (TieredBackend, Arc<MemoryBackend>) {
+        let limits = MemoryLimits {
+            max_entries: 100,
+            max_bytes: 4096,
+        };
+        let l2 = Arc::new(MemoryBackend::new(limits));
+        let backend = TieredBackend::new(l2.clone(), limits, Duration::from_secs(60));
+        (backend, l2)
+    }
+
+    #[tokio::test]
+    async fn l2_hits_populate_l1() {
+        let (cache, l2) = tiered();
+        l2.set("a", b"1", Some(Duration::from_secs(5)))
+            .await
+            .unwrap();
+
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"1".to_vec()));
+        assert_eq!(cache.get("b").await.unwrap(), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats["l1"]["hits"], 1);
+        assert_eq!(stats["l1"]["misses"], 2);
+        assert_eq!(stats["l2"]["hits"], 1);
+        assert_eq!(stats["l2"]["misses"], 1);
+    }
+
+    #[tokio::test]
+    async fn l1_copy_does_not_outlive_l2_ttl() {
+        let (cache, l2) = tiered();
+        l2.set("a", b"1", Some(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        cache.get("a").await.unwrap();
+
+        match cache.l1.ttl("a").await.unwrap() {
+            KeyTtl::Expires(ttl) => assert!(ttl <= Duration::from_millis(20)),
+            other => panic!("unexpected L1 ttl {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_invalidate_l1() {
+        let (cache, _) = tiered();
+        cache.set("a", b"1", None).await.unwrap();
+        cache.get("a").await.unwrap();
+        cache.set("a", b"2", None).await.unwrap();
+
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn writes_between_an_l2_read_and_the_fill_win() {
+        let (cache, l2) = tiered();
+        l2.set("a", b"1", None).await.unwrap();
+        let generation = cache.generation("a").load(Ordering::Acquire);
+        let read = l2.lookup("a").await.unwrap().unwrap();
+
+        cache.set("a", b"2", None).await.unwrap();
+        cache.fill_l1("a", &read.value, read.ttl, generation).await;
+        assert_eq!(cache.get("a").await.unwrap(), Some(b"2".to_vec()));
+
+        let generation = cache.generation("a").load(Ordering::Acquire);
+        let read = l2.lookup("a").await.unwrap().unwrap();
+        cache.delete("a").await.unwrap();
+        cache.fill_l1("a", &read.value, read.ttl, generation).await;
+        assert_eq!(cache.get("a").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn l1_hits_report_the_age_of_their_copy() {
+        let (cache, l2) = tiered();
+        l2.set("a", b"1", None).await.unwrap();
+        cache.lookup("a").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let hit = cache.lookup("a").await.unwrap().unwrap();
+        assert!(hit.age >= Duration::from_millis(20));
+        assert_eq!(hit.value, b"1");
+    }
+
+    #[tokio::test]
+    async fn l1_hits_report_the_l2_lifetime() {
+        let (cache, l2) = tiered();
+        l2.set("persistent", b"1", None).await.unwrap();
+        l2.set("hour", b"1", Some(Duration::from_secs(3600)))
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let hit = cache.lookup("persistent").await.unwrap().unwrap();
+            assert_eq!((hit.value, hit.ttl), (b"1".to_vec(), KeyTtl::Persistent));
+            let hit = cache.lookup("hour").await.unwrap().unwrap();
+            assert!(matches!(hit.ttl, KeyTtl::Expires(ttl) if ttl > Duration::from_secs(3590)));
+        }
+        let found = cache
+            .get_many_with_ttl(&["persistent", "hour"])
+            .await
+            .unwrap();
+        assert_eq!(found[0], Some((b"1".to_vec(), KeyTtl::Persistent)));
+        assert!(
+            matches!(found[1], Some((_, KeyTtl::Expires(ttl))) if ttl > Duration::from_secs(3590))
+        );
+        assert_eq!(cache.stats()["l1"]["hits"], 4);
+    }
+}
@@ -0,0 +1,300 @@
+use crate::metrics;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Thresholds of a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy)]
+pub struct BreakerSettings {
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// Time the circuit stays open before probing the backend again.
+    pub reset_timeout: Duration,
+    /// Requests allowed through at once while half-open.
+    pub half_open_max_requests: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BreakerState {
+    /// Normal operation, requests flow through.
+    Closed,
+    /// The backend is failing, requests are rejected.
+    Open,
+    /// Probing whether the backend has recovered.
+    HalfOpen,
+}
+
+struct Inner {
+    state: BreakerState,
+    /// Bumped on every transition so late results of earlier requests are ignored.
+    generation: u64,
+    consecutive_failures: u32,
+    opened_at: Instant,
+    half_open_in_flight: u32,
+}
+
+/// Circuit breaker guarding calls to a backend.
+///
+/// CLOSED opens after `failure_threshold` consecutive failures. OPEN
+/// rejects requests until `reset_timeout` has passed, then HALF-OPEN lets
+/// a limited number of probes through: a success closes the circuit, a
+/// failure opens it again.
+pub struct CircuitBreaker {
+    name: String,
+    settings: BreakerSettings,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: impl Into<String>, settings: BreakerSettings) -> Self {
+        let name = name.into();
+        metrics::CIRCUIT_BREAKER_STATE
+            .with_label_values(&[&name])
+            .set(BreakerState::Closed as i64);
+        Self {
+            name,
+            settings,
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                generation: 0,
+                consecutive_failures: 0,
+                opened_at: Instant::now(),
+                half_open_in_flight: 0,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        inner.state
+    }
+
+    /// Admits a request, or returns how long the caller should wait before retrying.
+    pub fn try_acquire(&self) -> Result<Permit<'_>, Duration> {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        match inner.state {
+            BreakerState::Closed => {}
+            BreakerState::Open => return Err(self.retry_after(&inner)),
+            BreakerState::HalfOpen => {
+                if inner.half_open_in_flight >= self.settings.half_open_max_requests {
+                    return Err(Duration::from_secs(1));
+                }
+                inner.half_open_in_flight += 1;
+            }
+        }
+        Ok(Permit {
+            breaker: self,
+            generation: inner.generation,
+            half_open: inner.state == BreakerState::HalfOpen,
+            recorded: false,
+        })
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        let mut inner = self.inner.lock().unwrap();
+        self.refresh(&mut inner);
+        let mut snapshot = json!({
+            "name": self.name,
+            "state": inner.state,
+            "consecutive_failures": inner.consecutive_failures,
+            "failure_threshold": self.settings.failure_threshold,
+            "reset_timeout_secs": self.settings.reset_timeout.as_secs_f64(),
+        });
+        if inner.state == BreakerState::Open {
+            snapshot["retry_after_secs"] = self.retry_after(&inner).as_secs().into();
+        }
+        snapshot
+    }
+
+    /// Moves an open circuit to half-open once the reset timeout has passed.
+    fn refresh(&self, inner: &mut Inner) {
+        if inner.state == BreakerState::Open
+            && inner.opened_at.elapsed() >= self.settings.reset_timeout
+        {
+            self.transition(inner, BreakerState::HalfOpen);
+        }
+    }
+
+    fn retry_after(&self, inner: &Inner) -> Duration {
+        let remaining = self
+            .settings
+            .reset_timeout
+            .saturating_sub(inner.opened_at.elapsed());
+        // Round up to whole seconds for the Retry-After header.
+        Duration::from_secs(remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0))
+            .max(Duration::from_secs(1))
+    }
+
+    fn record(&self, generation: u64, half_open: bool, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.generation != generation {
+            return;
+        }
+        if half_open {
+            inner.half_open_in_flight -= 1;
+        }
+        match (inner.state, success) {
+            (BreakerState::Closed, true) => inner.consecutive_failures = 0,
+            (BreakerState::Closed, false) => {
+                inner.consecutive_failures += 1;
+                if inner.consecutive_failures >= self.settings.failure_threshold {
+                    self.transition(&mut inner, BreakerState::Open);
+                }
+            }
+            (BreakerState::HalfOpen, true) => self.transition(&mut inner, BreakerState::Closed),
+            (BreakerState::HalfOpen, false) => self.transition(&mut inner, BreakerState::Open),
+            (BreakerState::Open, _) => {}
+        }
+    }
+
+    fn release(&self, generation: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.generation == generation {
+            inner.half_open_in_flight -= 1;
+        }
+    }
+
+    fn transition(&self, inner: &mut Inner, to: BreakerState) {
+        let from = inner.state;
+        inner.state = to;
+        inner.generation += 1;
+        inner.half_open_in_flight = 0;
+        metrics::CIRCUIT_BREAKER_STATE
+            .with_label_values(&[&self.name])
+            .set(to as i64);
+        match to {
+            BreakerState::Open => {
+                inner.opened_at = Instant::now();
+                tracing::warn!(
+                    "Circuit breaker '{}' state changed from {:?} to {:?} after {} consecutive failures",
+                    self.name,
+                    from,
+                    to,
+                    inner.consecutive_failures
+                );
+            }
+            BreakerState::Closed => {
+                inner.consecutive_failures = 0;
+                tracing::info!(
+                    "Circuit breaker '{}' state changed from {:?} to {:?}",
+                    self.name,
+                    from,
+                    to
+                );
+            }
+            BreakerState::HalfOpen => {
+                tracing::info!(
+                    "Circuit breaker '{}' state changed from {:?} to {:?}",
+                    self.name,
+                    from,
+                    to
+                );
+            }
+        }
+    }
+}
+
+/// Admission of a single request; report its outcome with [`Permit::record`].
+///
+/// A permit dropped without recording (e.g. a cancelled request) only
+/// frees its half-open slot.
+pub struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    generation: u64,
+    half_open: bool,
+    recorded: bool,
+}
+
+impl Permit<'_> {
+    pub fn record(mut self, success: bool) {
+        self.recorded = true;
+        self.breaker
+            .record(self.generation, self.half_open, success);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.half_open && !self.recorded {
+            self.breaker.release(self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32, reset_timeout: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(
+            "test",
+            BreakerSettings {
+                failure_threshold,
+                reset_timeout,
+                half_open_max_requests: 1,
+            },
+        )
+    }
+
+    #[test]
+    fn state_transitions() {
+        let cb = breaker(3, Duration::from_millis(50));
+        assert_eq!(cb.state(), BreakerState::Closed);
+
+        for _ in 0..3 {
+            cb.try_acquire().unwrap().record(false);
+        }
+        assert_eq!(cb.state(), BreakerState::Open);
+        assert!(cb.try_acquire().is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        let probe = cb.try_acquire().expect("probe allowed once half-open");
+        assert_eq!(cb.state(), BreakerState::HalfOpen);
+        assert!(cb.try_acquire().is_err(), "only one probe at a time");
+
+        probe.record(true);
+        assert_eq!(cb.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn successes_reset_the_failure_count() {
+        let cb = breaker(2, Duration::from_secs(10));
+        cb.try_acquire().unwrap().record(false);
+        cb.try_acquire().unwrap().record(true);
+        cb.try_acquire().unwrap().record(false);
+        assert_eq!(cb.state(), BreakerState::Closed);
+
+        cb.try_acquire().unwrap().record(false);
+        assert_eq!(cb.state(), BreakerState::Open);
+    }
+
+    #[test]
+    fn failed_probe_reopens_the_circuit() {
+        let cb = breaker(1, Duration::from_millis(20));
+        cb.try_acquire().unwrap().record(false);
+        std::thread::sleep(Duration::from_millis(30));
+
+        cb.try_acquire().unwrap().record(false);
+        assert_eq!(cb.state(), BreakerState::Open);
+        let retry_after = cb.try_acquire().err().unwrap();
+        assert_eq!(retry_after, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn dropped_probe_frees_its_slot() {
+        let cb = breaker(1, Duration::from_millis(20));
+        cb.try_acquire().unwrap().record(false);
+        std::thread::sleep(Duration::from_millis(30));
+
+        drop(cb.try_acquire().unwrap());
+        assert!(cb.try_acquire().is_ok());
+    }
+}
@@ -0,0 +1,241 @@
+//! Preconditions of conditional requests.
+//!
+//! A stored value is identified by its [`version`], reported as its `ETag`
+//! by reads and writes, so that a client can revalidate a copy it holds or
+//! later replace exactly that value.
+
+use crate::backend::{version, SetCondition};
+use crate::error::ApiError;
+use crate::value;
+use axum::http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
+
+/// Condition fields of a `POST /cache` entry; at most one may be set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WriteCondition {
+    /// Write only if the key does not exist yet.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub if_absent: bool,
+    /// Write only if the key exists.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub if_present: bool,
+    /// Write only if the stored value has this version, as given by `ETag`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_version: Option<String>,
+    /// Write only if the stored value equals this JSON value. Only values
+    /// written as JSON can match, not counters or raw values.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub if_value: Option<serde_json::Value>,
+}
+
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+impl WriteCondition {
+    pub fn resolve(&self) -> Result<Option<SetCondition>, String> {
+        match (
+            self.if_absent,
+            self.if_present,
+            &self.if_version,
+            &self.if_value,
+        ) {
+            (false, false, None, None) => Ok(None),
+            (true, false, None, None) => Ok(Some(SetCondition::Absent)),
+            (false, true, None, None) => Ok(Some(SetCondition::Present)),
+            (false, false, Some(version), None) => Ok(Some(SetCondition::Version(
+                version.trim_matches('"').to_string(),
+            ))),
+            (false, false, None, Some(previous)) => Ok(Some(SetCondition::Version(version(
+                &value::encode_json(previous),
+            )))),
+            _ => Err(
+                "at most one of if_absent, if_present, if_version or if_value may be set".into(),
+            ),
+        }
+    }
+}
+
+/// Reads the precondition of a `PUT /cache/:key` from `If-None-Match: *`,
+/// `If-Match: *` or `If-Match: "<version>"`.
+pub fn from_headers(headers: &HeaderMap) -> Result<Option<SetCondition>, String> {
+    let header = |name: header::HeaderName| {
+        headers
+            .get(&name)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map(str::trim)
+                    .map_err(|_| format!("invalid {}", name))
+            })
+            .transpose()
+    };
+    match (header(header::IF_NONE_MATCH)?, header(header::IF_MATCH)?) {
+        (None, None) => Ok(None),
+        (Some("*"), None) => Ok(Some(SetCondition::Absent)),
+        (Some(_), None) => Err("If-None-Match only supports \"*\" on writes".into()),
+        (None, Some("*")) => Ok(Some(SetCondition::Present)),
+        (None, Some(tag)) => match tag.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+            Some(version) if !version.contains('"') => {
+                Ok(Some(SetCondition::Version(version.to_string())))
+            }
+            _ => Err("If-Match must be \"*\" or a single strong ETag".into()),
+        },
+        (Some(_), Some(_)) => Err("If-None-Match and If-Match cannot be combined".into()),
+    }
+}
+
+/// `ETag` of a stored value.
+pub fn etag(stored: &[u8]) -> String {
+    format!("\"{}\"", version(stored))
+}
+
+/// Outcome of the `If-Match` / `If-None-Match` headers of a read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadCondition {
+    /// Serve the request as usual.
+    Proceed,
+    /// The client's copy is current; answer `304 Not Modified`.
+    NotModified,
+    /// `If-Match` does not hold; answer `412 Precondition Failed`.
+    Failed,
+}
+
+/// Evaluates the preconditions of a read against the `ETag` of the stored
+/// value, `None` if the key is missing.
+///
+/// `If-Match` uses the strong comparison and takes precedence;
+/// `If-None-Match` uses the weak one.
+pub fn check_read(headers: &HeaderMap, etag: Option<&str>) -> ReadCondition {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    if let Some(tags) = header(header::IF_MATCH) {
+        if !etag.is_some_and(|etag| lists(tags, etag, false)) {
+            return ReadCondition::Failed;
+        }
+    }
+    if let (Some(tags), Some(etag)) = (header(header::IF_NONE_MATCH), etag) {
+        if lists(tags, etag, true) {
+            return ReadCondition::NotModified;
+        }
+    }
+    ReadCondition::Proceed
+}
+
+/// Whether a comma-separated list of entity tags matches `etag`, with `*`
+/// matching any. The weak comparison ignores `W/` prefixes.
+fn lists(tags: &str, etag: &str, weak: bool) -> bool {
+    tags.split(',').map(str::trim).any(|tag| {
+        let tag = match tag.strip_prefix("W/") {
+            Some(tag) if weak => tag,
+            _ => tag,
+        };
+        tag == "*" || tag == etag
+    })
+}
+
+/// Error answered when `condition` did not hold for `key`.
+pub fn rejected(key: String, condition: &SetCondition) -> ApiError {
+    match condition {
+        SetCondition::Absent => ApiError::KeyExists(key),
+        SetCondition::Present => ApiError::PreconditionFailed(format!("key {:?} not found", key)),
+        SetCondition::Version(version) => {
+            ApiError::PreconditionFailed(format!("key {:?} does not hold version {}", key, version))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn condition(body: &str) -> Result<Option<SetCondition>, String> {
+        serde_json::from_str::<WriteCondition>(body)
+            .unwrap()
+            .resolve()
+    }
+
+    #[test]
+    fn resolves_body_conditions() {
+        assert_eq!(condition("{}"), Ok(None));
+        assert_eq!(
+            condition(r#"{"if_absent": true}"#),
+            Ok(Some(SetCondition::Absent))
+        );
+        assert_eq!(
+            condition(r#"{"if_version": "\"abc\""}"#),
+            Ok(Some(SetCondition::Version("abc".into())))
+        );
+        let previous = value::encode_json(&serde_json::json!({ "n": 1 }));
+        assert_eq!(
+            condition(r#"{"if_value": {"n": 1}}"#),
+            Ok(Some(SetCondition::Version(version(&previous))))
+        );
+        assert!(condition(r#"{"if_absent": true, "if_version": "abc"}"#).is_err());
+    }
+
+    #[test]
+    fn reads_conditional_request_headers() {
+        let headers = |name, value| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(from_headers(&HeaderMap::new()), Ok(None));
+        assert_eq!(
+            from_headers(&headers(header::IF_NONE_MATCH, "*")),
+            Ok(Some(SetCondition::Absent))
+        );
+        assert_eq!(
+            from_headers(&headers(header::IF_MATCH, "*")),
+            Ok(Some(SetCondition::Present))
+        );
+        assert_eq!(
+            from_headers(&headers(header::IF_MATCH, "\"abc\"")),
+            Ok(Some(SetCondition::Version("abc".into())))
+        );
+        assert!(from_headers(&headers(header::IF_MATCH, "W/\"abc\"")).is_err());
+        assert!(from_headers(&headers(header::IF_MATCH, "\"a\", \"b\"")).is_err());
+    }
+
+    #[test]
+    fn evaluates_read_preconditions() {
+        let etag = etag(b"value");
+        let check = |name, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+            (
+                check_read(&headers, Some(&etag)),
+                check_read(&headers, None),
+            )
+        };
+        assert_eq!(
+            check_read(&HeaderMap::new(), Some(&etag)),
+            ReadCondition::Proceed
+        );
+
+        let listed = format!("\"other\", W/{}", etag);
+        assert_eq!(
+            check(header::IF_NONE_MATCH, &listed),
+            (ReadCondition::NotModified, ReadCondition::Proceed)
+        );
+        assert_eq!(
+            check(header::IF_NONE_MATCH, "\"other\""),
+            (ReadCondition::Proceed, ReadCondition::Proceed)
+        );
+
+        assert_eq!(
+            check(header::IF_MATCH, &etag),
+            (ReadCondition::Proceed, ReadCondition::Failed)
+        );
+        assert_eq!(
+            check(header::IF_MATCH, &format!("W/{}", etag)).0,
+            ReadCondition::Failed,
+            "If-Match uses the strong comparison"
+        );
+        assert_eq!(
+            check(header::IF_MATCH, "*"),
+            (ReadCondition::Proceed, ReadCondition::Failed)
+        );
+    }
+}
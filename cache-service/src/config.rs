@@ -0,0 +1,255 @@
+use crate::auth::AuthSettings;
+use crate::backend::{ConnectionSettings, MemoryLimits};
+use crate::circuit_breaker::BreakerSettings;
+use crate::namespace::NamespaceSettings;
+use crate::telemetry::{TraceExporter, TracingSettings};
+use crate::value::LegacyValues;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Storage the service runs against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    Redis,
+    Memory,
+    Cluster,
+    Sentinel,
+    Sharded,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "redis" => Ok(BackendKind::Redis),
+            "memory" => Ok(BackendKind::Memory),
+            "cluster" => Ok(BackendKind::Cluster),
+            "sentinel" => Ok(BackendKind::Sentinel),
+            "sharded" => Ok(BackendKind::Sharded),
+            other => Err(format!("unknown backend {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid value {value:?} for {var}: {reason}")]
+pub struct ConfigError {
+    var: &'static str,
+    value: String,
+    reason: String,
+}
+
+/// Service configuration, read from environment variables at startup.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// `CACHE_BACKEND`: `redis` (default), `memory`, `cluster`, `sentinel`
+    /// or `sharded`.
+    pub backend: BackendKind,
+    /// `REDIS_URL`: connection string used by the Redis backend.
+    pub redis_url: String,
+    /// `REDIS_CLUSTER_NODES`: comma-separated seed nodes of the cluster backend.
+    pub redis_cluster_nodes: Vec<String>,
+    /// `REDIS_SENTINELS`: comma-separated sentinel addresses of the sentinel backend.
+    pub redis_sentinels: Vec<String>,
+    /// `REDIS_SENTINEL_SERVICE`: name of the primary monitored by the sentinels.
+    pub redis_sentinel_service: String,
+    /// `REDIS_SENTINEL_CHECK_SECS`: seconds between re-checks of the resolved
+    /// primary, at least 1.
+    pub redis_sentinel_check_interval: Duration,
+    /// `REDIS_SHARDS`: comma-separated Redis URLs of the sharded backend.
+    pub redis_shards: Vec<String>,
+    /// `SHARD_VIRTUAL_NODES`: points each shard occupies on the hash ring.
+    pub shard_virtual_nodes: usize,
+    /// `REDIS_MAX_IN_FLIGHT`, `REDIS_COMMAND_TIMEOUT_MS`, `REDIS_RECONNECT_BACKOFF_MS`,
+    /// `REDIS_RECONNECT_RETRIES` and `REDIS_CONFIGURE_NOTIFICATIONS`: tuning of
+    /// the shared Redis connection. The first two must be at least 1.
+    pub redis_connection: ConnectionSettings,
+    /// `BREAKER_ENABLED`: guard Redis calls with a circuit breaker.
+    pub breaker_enabled: bool,
+    /// `BREAKER_FAILURE_THRESHOLD`, `BREAKER_RESET_TIMEOUT_SECS` and
+    /// `BREAKER_HALF_OPEN_MAX_REQUESTS`: circuit breaker thresholds, each at
+    /// least 1.
+    pub breaker: BreakerSettings,
+    /// `MEMORY_MAX_ENTRIES` / `MEMORY_MAX_BYTES`: budget of the memory backend.
+    pub memory: MemoryLimits,
+    /// `L1_ENABLED`: put an in-process L1 in front of Redis.
+    pub l1_enabled: bool,
+    /// `L1_MAX_ENTRIES` / `L1_MAX_BYTES`: budget of the L1 tier.
+    pub l1: MemoryLimits,
+    /// `L1_MAX_TTL_SECS`: longest time an entry stays in L1.
+    pub l1_max_ttl: Duration,
+    /// `LEGACY_VALUE_MODE`: return unmarked strings stored by earlier versions
+    /// as `string` (default) or `parse` the JSON they hold.
+    pub legacy_values: LegacyValues,
+    /// `NAMESPACES`: `;`-separated namespaces with their limits, such as
+    /// `billing:default_ttl=5m,max_ttl=1h;search:max_value_bytes=65536`.
+    pub namespaces: Vec<NamespaceSettings>,
+    /// `AUTH_API_KEYS_FILE`, `AUTH_JWT_HS256_SECRET_FILE`,
+    /// `AUTH_JWT_RS256_PUBLIC_KEY_FILE`, `AUTH_JWT_ISSUER` and
+    /// `AUTH_JWT_AUDIENCE`: credentials accepted from callers.
+    pub auth: AuthSettings,
+    /// `ACL_POLICY_FILE`: JSON policy granting identities access to key
+    /// prefixes; reloaded on `SIGHUP`.
+    pub acl_policy_file: Option<String>,
+    /// `OTEL_TRACES_EXPORTER` (`none`, `otlp`, `stdout` or `file`),
+    /// `OTEL_SERVICE_NAME` and `OTEL_TRACES_FILE`: span export.
+    pub tracing: TracingSettings,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Ok(Config {
+            backend: parse_env("CACHE_BACKEND", BackendKind::Redis)?,
+            redis_url: get_env("REDIS_URL", "redis://redis:6379"),
+            redis_cluster_nodes: get_list("REDIS_CLUSTER_NODES"),
+            redis_sentinels: get_list("REDIS_SENTINELS"),
+            redis_sentinel_service: get_env("REDIS_SENTINEL_SERVICE", "mymaster"),
+            redis_sentinel_check_interval: Duration::from_secs(parse_nonzero(
+                "REDIS_SENTINEL_CHECK_SECS",
+                5,
+            )?),
+            redis_shards: get_list("REDIS_SHARDS"),
+            shard_virtual_nodes: parse_nonzero("SHARD_VIRTUAL_NODES", 160)?,
+            redis_connection: ConnectionSettings {
+                max_in_flight: parse_nonzero("REDIS_MAX_IN_FLIGHT", 1024)?,
+                command_timeout: Duration::from_millis(parse_nonzero(
+                    "REDIS_COMMAND_TIMEOUT_MS",
+                    2000,
+                )?),
+                reconnect_backoff: Duration::from_millis(parse_env(
+                    "REDIS_RECONNECT_BACKOFF_MS",
+                    100,
+                )?),
+                reconnect_retries: parse_env("REDIS_RECONNECT_RETRIES", 6)?,
+                configure_notifications: parse_env("REDIS_CONFIGURE_NOTIFICATIONS", false)?,
+            },
+            breaker_enabled: parse_env("BREAKER_ENABLED", true)?,
+            breaker: BreakerSettings {
+                failure_threshold: parse_nonzero("BREAKER_FAILURE_THRESHOLD", 3)?,
+                reset_timeout: Duration::from_secs(parse_nonzero(
+                    "BREAKER_RESET_TIMEOUT_SECS",
+                    10,
+                )?),
+                half_open_max_requests: parse_nonzero("BREAKER_HALF_OPEN_MAX_REQUESTS", 1)?,
+            },
+            memory: MemoryLimits {
+                max_entries: parse_env("MEMORY_MAX_ENTRIES", 100_000)?,
+                max_bytes: parse_env("MEMORY_MAX_BYTES", 256 * 1024 * 1024)?,
+            },
+            l1_enabled: parse_env("L1_ENABLED", false)?,
+            l1: MemoryLimits {
+                max_entries: parse_env("L1_MAX_ENTRIES", 10_000)?,
+                max_bytes: parse_env("L1_MAX_BYTES", 64 * 1024 * 1024)?,
+            },
+            l1_max_ttl: Duration::from_secs(parse_env("L1_MAX_TTL_SECS", 30)?),
+            legacy_values: parse_env("LEGACY_VALUE_MODE", LegacyValues::String)?,
+            namespaces: parse_entries("NAMESPACES", ';')?,
+            auth: AuthSettings {
+                api_keys_file: get_optional("AUTH_API_KEYS_FILE"),
+                jwt_hs256_secret_file: get_optional("AUTH_JWT_HS256_SECRET_FILE"),
+                jwt_rs256_public_key_file: get_optional("AUTH_JWT_RS256_PUBLIC_KEY_FILE"),
+                jwt_issuer: get_optional("AUTH_JWT_ISSUER"),
+                jwt_audience: get_optional("AUTH_JWT_AUDIENCE"),
+            },
+            acl_policy_file: get_optional("ACL_POLICY_FILE"),
+            tracing: TracingSettings {
+                exporter: parse_env("OTEL_TRACES_EXPORTER", TraceExporter::None)?,
+                service_name: get_env("OTEL_SERVICE_NAME", "cache-service"),
+                file: get_env("OTEL_TRACES_FILE", "traces.jsonl"),
+            },
+        })
+    }
+}
+
+fn get_env(var: &str, default: &str) -> String {
+    std::env::var(var).unwrap_or_else(|_| default.into())
+}
+
+fn get_optional(var: &str) -> Option<String> {
+    std::env::var(var).ok().filter(|value| !value.is_empty())
+}
+
+fn get_list(var: &str) -> Vec<String> {
+    get_env(var, "")
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_entries<T>(var: &'static str, separator: char) -> Result<Vec<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    get_env(var, "")
+        .split(separator)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry.parse().map_err(|e: T::Err| ConfigError {
+                var,
+                value: entry.to_string(),
+                reason: e.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn parse_env<T>(var: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match std::env::var(var) {
+        Ok(value) => value.parse().map_err(|e: T::Err| ConfigError {
+            var,
+            reason: e.to_string(),
+            value,
+        }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// [`parse_env`] for settings that must be at least 1.
+fn parse_nonzero<T>(var: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr + Default + PartialEq + std::fmt::Display,
+    T::Err: std::fmt::Display,
+{
+    let value = parse_env(var, default)?;
+    if value == T::default() {
+        return Err(ConfigError {
+            var,
+            value: value.to_string(),
+            reason: "must be at least 1".into(),
+        });
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads the configuration with `var` set to `value`, returning the
+    /// variable it was refused for, if any.
+    fn refused_var(var: &str, value: &str) -> Option<&'static str> {
+        std::env::set_var(var, value);
+        let result = Config::from_env();
+        std::env::remove_var(var);
+        result.err().map(|e| e.var)
+    }
+
+    #[test]
+    fn rejects_zero_where_at_least_1_is_needed() {
+        let var = "REDIS_SENTINEL_CHECK_SECS";
+        assert_eq!(refused_var(var, "0"), Some(var));
+        assert_eq!(refused_var(var, "1"), None);
+        for var in ["REDIS_MAX_IN_FLIGHT", "REDIS_COMMAND_TIMEOUT_MS"] {
+            assert_eq!(refused_var(var, "0"), Some(var));
+        }
+    }
+}
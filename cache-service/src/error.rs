@@ -0,0 +1,200 @@
+use crate::acl::Denial;
+use crate::backend::BackendError;
+use crate::value::ValueError;
+use axum::extract::rejection::{BytesRejection, JsonRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Errors returned by the HTTP API.
+///
+/// Every error is answered with a [`ErrorBody`] under `"error"`, so clients
+/// can branch on `code` rather than on the message.
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("key {0:?} not found")]
+    Miss(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    InvalidTtl(String),
+    #[error("{0}")]
+    PayloadTooLarge(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(Box<Denial>),
+    #[error("key {0:?} already exists")]
+    KeyExists(String),
+    #[error("{0}")]
+    PreconditionFailed(String),
+    #[error("value of key {0:?} is not a number, or the result would overflow")]
+    NotCounter(String),
+    #[error("value of key {key:?} cannot be returned as JSON ({reason}); read it alone instead")]
+    NotRepresentable { key: String, reason: String },
+    #[error("cache backend unavailable: {reason}")]
+    Unavailable {
+        reason: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("cache backend did not respond within {0:?}")]
+    Timeout(Duration),
+    #[error("{0}")]
+    Internal(String),
+}
+
+/// JSON description of an error.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    /// Stable, machine-readable error code.
+    pub code: String,
+    pub message: String,
+    /// Access that was refused, on `forbidden` errors.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denied: Option<Denial>,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::Miss(_) | ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) | ApiError::InvalidTtl(_) => StatusCode::BAD_REQUEST,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::KeyExists(_) => StatusCode::CONFLICT,
+            ApiError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ApiError::NotCounter(_) => StatusCode::CONFLICT,
+            ApiError::NotRepresentable { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Miss(_) => "cache_miss",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::InvalidTtl(_) => "invalid_ttl",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::KeyExists(_) => "key_exists",
+            ApiError::PreconditionFailed(_) => "precondition_failed",
+            ApiError::NotCounter(_) => "not_a_counter",
+            ApiError::NotRepresentable { .. } => "not_representable",
+            ApiError::Unavailable { .. } => "backend_unavailable",
+            ApiError::Timeout(_) => "backend_timeout",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code().into(),
+            message: self.to_string(),
+            denied: match self {
+                ApiError::Forbidden(denial) => Some((**denial).clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl From<BackendError> for ApiError {
+    fn from(e: BackendError) -> Self {
+        match e {
+            BackendError::Timeout(timeout) => ApiError::Timeout(timeout),
+            BackendError::EntryTooLarge { .. } => ApiError::PayloadTooLarge(e.to_string()),
+            BackendError::ReservedKey { .. } | BackendError::InvalidCursor(_) => {
+                ApiError::BadRequest(e.to_string())
+            }
+            BackendError::NotCounter(key) => ApiError::NotCounter(key),
+            BackendError::CircuitOpen { retry_after, .. } => ApiError::Unavailable {
+                reason: e.to_string(),
+                retry_after: Some(retry_after),
+            },
+            e if e.is_unavailable() => ApiError::Unavailable {
+                reason: e.to_string(),
+                retry_after: None,
+            },
+            e => ApiError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<ValueError> for ApiError {
+    fn from(e: ValueError) -> Self {
+        ApiError::Internal(format!("cached value cannot be returned: {}", e))
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        rejected(rejection.status(), rejection.body_text())
+    }
+}
+
+fn rejected(status: StatusCode, message: String) -> ApiError {
+    if status == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge(message)
+    } else {
+        ApiError::BadRequest(message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        match &self {
+            ApiError::Unavailable { .. } | ApiError::Timeout(_) => tracing::warn!("{}", self),
+            _ if status.is_server_error() => tracing::error!("{}", self),
+            _ => {}
+        }
+        let body = Json(serde_json::json!({ "error": self.body() }));
+        match self {
+            ApiError::Unavailable {
+                retry_after: Some(retry_after),
+                ..
+            } => (
+                status,
+                [(header::RETRY_AFTER, retry_after.as_secs().to_string())],
+                body,
+            )
+                .into_response(),
+            ApiError::Unauthorized(_) => {
+                (status, [(header::WWW_AUTHENTICATE, "Bearer")], body).into_response()
+            }
+            _ => (status, body).into_response(),
+        }
+    }
+}
+
+/// [`axum::Json`] answering malformed bodies with an [`ApiError`].
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// [`axum::extract::Query`] answering malformed queries with an [`ApiError`].
+#[derive(Debug, FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
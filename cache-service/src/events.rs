@@ -0,0 +1,106 @@
+//! Stream of key changes served at `/events`.
+//!
+//! One subscription to the backend per process is shared by every client,
+//! each receiving the events of its namespace and prefix that it may read.
+
+use crate::acl::{Access, Operation};
+use crate::backend::{CacheBackend, KeyEvent};
+use crate::namespace::Namespace;
+use axum::response::sse::Event;
+use futures::{future, Stream, StreamExt};
+use std::convert::Infallible;
+use std::sync::{Arc, Once};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Events buffered for clients that fall behind before they miss some.
+const BUFFER: usize = 4096;
+const MIN_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(30);
+
+/// Relays the events of the backend to the clients of `/events`.
+pub struct EventHub {
+    backend: Arc<dyn CacheBackend>,
+    sender: broadcast::Sender<KeyEvent>,
+    subscribed: Once,
+}
+
+impl EventHub {
+    pub fn new(backend: Arc<dyn CacheBackend>) -> Self {
+        Self {
+            backend,
+            sender: broadcast::channel(BUFFER).0,
+            subscribed: Once::new(),
+        }
+    }
+
+    /// Receives the events of the backend, subscribing to it on first use.
+    pub fn subscribe(&self) -> broadcast::Receiver<KeyEvent> {
+        self.subscribed.call_once(|| {
+            tokio::spawn(relay(self.backend.clone(), self.sender.clone()));
+        });
+        self.sender.subscribe()
+    }
+
+    /// Server-sent events of the keys of `ns` starting with `prefix` that
+    /// `access` allows reading: one `set`, `delete`, `expire` or `evict`
+    /// event per change, and a `lagged` event when the client fell behind
+    /// and missed some.
+    pub fn stream(
+        &self,
+        ns: Namespace,
+        access: Access,
+        prefix: String,
+    ) -> impl Stream<Item = Result<Event, Infallible>> {
+        let events = futures::stream::unfold(self.subscribe(), |mut events| async move {
+            match events.recv().await {
+                Err(broadcast::error::RecvError::Closed) => None,
+                received => Some((received, events)),
+            }
+        });
+        events.filter_map(move |received| {
+            let event = match received {
+                Ok(event) => ns
+                    .local_key(&event.key)
+                    .filter(|key| key.starts_with(prefix.as_str()))
+                    .filter(|key| access.check(Operation::Read, &ns, key).is_ok())
+                    .and_then(|key| {
+                        let local = KeyEvent {
+                            kind: event.kind,
+                            key: key.to_string(),
+                        };
+                        Event::default()
+                            .event(event.kind.as_str())
+                            .json_data(local)
+                            .ok()
+                    }),
+                Err(broadcast::error::RecvError::Lagged(missed)) => Event::default()
+                    .event("lagged")
+                    .json_data(serde_json::json!({ "missed": missed }))
+                    .ok(),
+                Err(broadcast::error::RecvError::Closed) => None,
+            };
+            future::ready(event.map(Ok))
+        })
+    }
+}
+
+/// Forwards the events of `backend`, subscribing again with a growing
+/// delay whenever the subscription fails or is lost.
+async fn relay(backend: Arc<dyn CacheBackend>, sender: broadcast::Sender<KeyEvent>) {
+    let mut delay = MIN_RESUBSCRIBE_DELAY;
+    loop {
+        match backend.watch().await {
+            Ok(mut events) => {
+                delay = MIN_RESUBSCRIBE_DELAY;
+                while let Some(event) = events.next().await {
+                    let _ = sender.send(event);
+                }
+                tracing::warn!("Key event subscription lost; subscribing again");
+            }
+            Err(e) => tracing::warn!("Failed to subscribe to key events: {}", e),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+    }
+}
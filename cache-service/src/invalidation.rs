@@ -0,0 +1,304 @@
+//! Bulk invalidation of the keys carrying a tag or starting with a prefix.
+//!
+//! Invalidations can cover many keys, so they run as background jobs whose
+//! progress is polled at `/invalidations/:id`.
+
+use crate::acl::{Access, Operation};
+use crate::backend::{escape_glob, BackendResult, ScanPage};
+use crate::error::ApiError;
+use crate::namespace::Namespace;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Most tags a single entry may carry.
+pub const MAX_TAGS: usize = 32;
+/// Keys visited per backend call while invalidating.
+const PAGE_SIZE: usize = 500;
+/// Finished jobs kept for polling; older ones are forgotten.
+const RETAINED_JOBS: usize = 100;
+
+/// Checks the tags of a write.
+pub fn check_tags(tags: &[String]) -> Result<(), ApiError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ApiError::BadRequest(format!(
+            "{} tags exceed the limit of {}",
+            tags.len(),
+            MAX_TAGS
+        )));
+    }
+    if tags.iter().any(String::is_empty) {
+        return Err(ApiError::BadRequest("tags must not be empty".into()));
+    }
+    Ok(())
+}
+
+/// Keys an invalidation removes; the body of `POST /invalidations`, such as
+/// `{"tag": "product:42"}` or `{"prefix": "product:42:"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Tag(String),
+    Prefix(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+struct Outcome {
+    finished_at: SystemTime,
+    error: Option<String>,
+}
+
+/// Invalidation running or run in the background.
+pub struct Job {
+    pub id: u64,
+    pub namespace: String,
+    pub target: Target,
+    /// Subject of the caller that started the job, when authenticated.
+    pub started_by: Option<String>,
+    started_at: SystemTime,
+    scanned: AtomicU64,
+    deleted: AtomicU64,
+    denied: AtomicU64,
+    outcome: OnceLock<Outcome>,
+}
+
+/// Progress of a [`Job`], as reported by the `/invalidations` endpoints.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobReport {
+    pub id: u64,
+    pub namespace: String,
+    #[serde(flatten)]
+    pub target: Target,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_by: Option<String>,
+    pub status: JobStatus,
+    /// Keys visited so far.
+    pub scanned: u64,
+    /// Keys removed so far; keys that expired meanwhile are not counted.
+    pub deleted: u64,
+    /// Keys left in place because the caller may not delete them.
+    pub denied: u64,
+    /// RFC 3339 timestamps.
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Job {
+    fn new(id: u64, namespace: &str, target: Target, started_by: Option<&str>) -> Self {
+        Self {
+            id,
+            namespace: namespace.to_string(),
+            target,
+            started_by: started_by.map(str::to_string),
+            started_at: SystemTime::now(),
+            scanned: AtomicU64::new(0),
+            deleted: AtomicU64::new(0),
+            denied: AtomicU64::new(0),
+            outcome: OnceLock::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.outcome.get().is_some()
+    }
+
+    pub fn report(&self) -> JobReport {
+        let outcome = self.outcome.get();
+        let status = match outcome {
+            None => JobStatus::Running,
+            Some(Outcome { error: None, .. }) => JobStatus::Completed,
+            Some(Outcome { error: Some(_), .. }) => JobStatus::Failed,
+        };
+        JobReport {
+            id: self.id,
+            namespace: self.namespace.clone(),
+            target: self.target.clone(),
+            started_by: self.started_by.clone(),
+            status,
+            scanned: self.scanned.load(Ordering::Relaxed),
+            deleted: self.deleted.load(Ordering::Relaxed),
+            denied: self.denied.load(Ordering::Relaxed),
+            started_at: humantime::format_rfc3339_millis(self.started_at).to_string(),
+            finished_at: outcome
+                .map(|outcome| humantime::format_rfc3339_millis(outcome.finished_at).to_string()),
+            error: outcome.and_then(|outcome| outcome.error.clone()),
+        }
+    }
+
+    /// Removes the targeted keys of `ns` that `access` allows deleting,
+    /// a page at a time.
+    async fn invalidate(&self, ns: &Namespace, access: &Access) -> BackendResult<()> {
+        let mut cursor: Option<String> = None;
+        loop {
+            let page: ScanPage = match &self.target {
+                Target::Tag(tag) => ns.backend.tagged(tag, cursor.as_deref(), PAGE_SIZE).await?,
+                Target::Prefix(prefix) => {
+                    let pattern = format!("{}*", escape_glob(prefix));
+                    ns.backend
+                        .scan(cursor.as_deref(), &pattern, PAGE_SIZE)
+                        .await?
+                }
+            };
+            self.scanned
+                .fetch_add(page.keys.len() as u64, Ordering::Relaxed);
+            let (allowed, denied): (Vec<&str>, Vec<&str>) = page
+                .keys
+                .iter()
+                .map(String::as_str)
+                .partition(|key| access.check(Operation::Delete, ns, key).is_ok());
+            self.denied
+                .fetch_add(denied.len() as u64, Ordering::Relaxed);
+            let removed = try_join_all(allowed.iter().map(|key| ns.backend.delete(key))).await?;
+            self.deleted.fetch_add(
+                removed.into_iter().filter(|&existed| existed).count() as u64,
+                Ordering::Relaxed,
+            );
+            // Keys the caller may not delete stay tagged. Indexes do not
+            // expire, so this is also what drops keys that are gone.
+            if let Target::Tag(tag) = &self.target {
+                ns.backend.untag(tag, &allowed).await?;
+            }
+            cursor = match page.cursor {
+                Some(next) => Some(next),
+                None => return Ok(()),
+            };
+        }
+    }
+}
+
+/// Invalidation jobs, most recent last.
+#[derive(Default)]
+pub struct Invalidations {
+    next_id: AtomicU64,
+    jobs: Mutex<VecDeque<Arc<Job>>>,
+}
+
+impl Invalidations {
+    /// Starts invalidating `target` in `ns` in the background, with the
+    /// permissions of the caller behind `access`.
+    pub fn start(&self, ns: Namespace, access: Access, target: Target) -> Arc<Job> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = Arc::new(Job::new(id, ns.name(), target, access.subject()));
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            jobs.push_back(job.clone());
+            while jobs.len() > RETAINED_JOBS {
+                let Some(oldest) = jobs.iter().position(|job| job.is_finished()) else {
+                    break;
+                };
+                jobs.remove(oldest);
+            }
+        }
+
+        let running = job.clone();
+        tokio::spawn(async move {
+            let result = running.invalidate(&ns, &access).await;
+            let report = running.report();
+            match &result {
+                Ok(()) => tracing::info!(
+                    "Invalidation {} of {:?} deleted {} of {} keys",
+                    running.id,
+                    running.target,
+                    report.deleted,
+                    report.scanned
+                ),
+                Err(e) => tracing::warn!("Invalidation {} failed: {}", running.id, e),
+            }
+            let _ = running.outcome.set(Outcome {
+                finished_at: SystemTime::now(),
+                error: result.err().map(|e| e.to_string()),
+            });
+        });
+        job
+    }
+
+    pub fn get(&self, id: u64) -> Option<Arc<Job>> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    /// Jobs of the namespace named `namespace`, oldest first.
+    pub fn list(&self, namespace: &str) -> Vec<Arc<Job>> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.iter()
+            .filter(|job| job.namespace == namespace)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{CacheBackend, MemoryBackend, MemoryLimits};
+    use crate::namespace::Namespaces;
+    use std::time::Duration;
+
+    async fn finished(job: &Job) -> JobReport {
+        for _ in 0..100 {
+            if job.is_finished() {
+                return job.report();
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("invalidation {} did not finish", job.id);
+    }
+
+    #[tokio::test]
+    async fn invalidates_tagged_keys_and_prefixes() {
+        let backend: Arc<dyn CacheBackend> = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 100,
+            max_bytes: 4096,
+        }));
+        let ns = Namespaces::new(backend, &[])
+            .get("default")
+            .unwrap()
+            .clone();
+        for key in ["product:1", "product:1:price", "product:2", "list:all"] {
+            ns.backend.set(key, b"1", None).await.unwrap();
+        }
+        ns.backend.tag("product:1", &["p1"]).await.unwrap();
+        ns.backend.tag("list:all", &["p1", "p2"]).await.unwrap();
+        let jobs = Invalidations::default();
+
+        let job = jobs.start(
+            ns.clone(),
+            Access::new(None, None),
+            Target::Tag("p1".into()),
+        );
+        let report = finished(&job).await;
+        assert_eq!(report.status, JobStatus::Completed);
+        assert_eq!((report.scanned, report.deleted), (2, 2));
+        assert_eq!(ns.backend.get("list:all").await.unwrap(), None);
+        assert!(ns
+            .backend
+            .tagged("p1", None, 10)
+            .await
+            .unwrap()
+            .keys
+            .is_empty());
+
+        let job = jobs.start(
+            ns.clone(),
+            Access::new(None, None),
+            Target::Prefix("product:".into()),
+        );
+        let report = finished(&job).await;
+        assert_eq!((report.scanned, report.deleted), (2, 2));
+        assert_eq!(ns.backend.key_count().await.unwrap(), 0);
+        assert_eq!(jobs.list("default").len(), 2);
+        assert_eq!(jobs.get(job.id).unwrap().id, 2);
+    }
+}
@@ -0,0 +1,1524 @@
+use axum::{
+    routing::{get, post},
+    Router,
+    Json,
+    http::{header, HeaderMap, StatusCode},
+    extract::{rejection::BytesRejection, Path},
+    body::Bytes,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use tower_http::trace::TraceLayer;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+pub mod acl;
+pub mod auth;
+pub mod backend;
+pub mod circuit_breaker;
+pub mod condition;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod invalidation;
+pub mod metrics;
+pub mod namespace;
+pub mod telemetry;
+pub mod ttl;
+pub mod value;
+
+use acl::{Access, Acl, Operation};
+use auth::Authenticator;
+use backend::{
+    BackendError, CacheBackend, Counter, Hit, KeyTtl, SetCondition, SetItem, ShardedBackend,
+};
+use circuit_breaker::CircuitBreaker;
+use condition::{ReadCondition, WriteCondition};
+use error::{ApiError, ApiJson, ApiQuery, ErrorBody};
+use events::EventHub;
+use invalidation::{Invalidations, JobReport, Target};
+use namespace::{Namespace, Namespaces};
+use ttl::{TtlReport, TtlUpdate};
+use value::{LegacyValues, StoredValue};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub ttl: Option<u64>,
+    /// Groups the entry can be invalidated by.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(flatten)]
+    pub condition: WriteCondition,
+}
+
+/// Query of `PUT /cache/:key`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawEntryParams {
+    pub ttl: Option<u64>,
+    /// Comma-separated tags.
+    pub tags: Option<String>,
+}
+
+impl RawEntryParams {
+    pub fn tags(&self) -> Vec<String> {
+        match &self.tags {
+            Some(tags) => tags.split(',').map(|tag| tag.trim().to_string()).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Body of `DELETE /cache`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteKeys {
+    pub keys: Vec<String>,
+}
+
+/// Keys that were removed by `DELETE /cache` and keys that did not exist.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeleteReport {
+    pub deleted: Vec<String>,
+    pub missing: Vec<String>,
+}
+
+/// Body of `POST /cache/batch/get`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchGet {
+    pub keys: Vec<String>,
+}
+
+/// Values found by `POST /cache/batch/get`, the keys that missed, and the
+/// keys whose value cannot be returned as JSON, such as binary raw values.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchGetReport {
+    pub values: HashMap<String, serde_json::Value>,
+    pub misses: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub errors: HashMap<String, ErrorBody>,
+}
+
+/// Body of `POST /cache/batch/set`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchSet {
+    pub entries: Vec<CacheEntry>,
+}
+
+/// Outcome of each entry of `POST /cache/batch/set`, in request order.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchSetReport {
+    pub results: Vec<SetStatus>,
+}
+
+/// Query of `GET /cache`; `prefix` and `match` are exclusive.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListKeysParams {
+    pub prefix: Option<String>,
+    /// Glob pattern such as `user:*` or `session:[0-9]?`.
+    #[serde(rename = "match")]
+    pub pattern: Option<String>,
+    /// Cursor returned by the previous page.
+    pub cursor: Option<String>,
+    /// Maximum number of keys per page, capped at [`MAX_PAGE_SIZE`].
+    pub limit: Option<usize>,
+    /// Whether to report the remaining lifetime of each key.
+    #[serde(default)]
+    pub ttl: bool,
+    /// Whether to report the size of each value.
+    #[serde(default)]
+    pub size: bool,
+}
+
+/// Page of keys listed by `GET /cache`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyListing {
+    pub keys: Vec<ListedKey>,
+    /// Cursor of the next page, `null` once every key was listed.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListedKey {
+    pub key: String,
+    /// Remaining lifetime, `null` when the key never expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_ms: Option<Option<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+}
+
+/// Body of `POST /cache/:key/incr` and `POST /cache/:key/decr`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CounterUpdate {
+    /// Integer or float amount, 1 unless given.
+    #[serde(default = "CounterUpdate::default_amount")]
+    pub by: Counter,
+    /// Seconds until a counter created by the update expires.
+    pub ttl: Option<u64>,
+}
+
+impl CounterUpdate {
+    fn default_amount() -> Counter {
+        Counter::Integer(1)
+    }
+}
+
+/// Value of a counter after an update.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CounterValue {
+    pub key: String,
+    pub value: Counter,
+}
+
+/// Keys listed per page of `GET /cache` unless a `limit` is given.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+/// Largest `limit` of `GET /cache`.
+pub const MAX_PAGE_SIZE: usize = 1000;
+/// Backend scans a page of `GET /cache` may take, so that sparse matches
+/// still return promptly, possibly with fewer keys than asked for.
+const MAX_SCAN_ROUNDS: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetStatus {
+    pub key: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub backend: Arc<dyn CacheBackend>,
+    /// Set when keys are sharded client-side, for `/admin/ring`.
+    pub ring: Option<Arc<ShardedBackend>>,
+    /// Circuit breakers guarding the backend, for `/admin/circuit-breakers`.
+    pub breakers: Vec<Arc<CircuitBreaker>>,
+    /// How strings written before values were marked are returned.
+    pub legacy_values: LegacyValues,
+    /// Namespaces addressed by the cache routes.
+    pub namespaces: Arc<Namespaces>,
+    /// Verifies callers' credentials; requests are not authenticated when unset.
+    pub auth: Option<Arc<Authenticator>>,
+    /// Policy restricting authenticated callers to some keys and operations.
+    pub acl: Option<Arc<Acl>>,
+    /// Bulk invalidations started through `/invalidations`.
+    pub invalidations: Arc<Invalidations>,
+    /// Key changes relayed to the clients of `/events`.
+    pub events: Arc<EventHub>,
+}
+
+impl AppState {
+    pub fn new(backend: Arc<dyn CacheBackend>) -> Self {
+        Self {
+            namespaces: Arc::new(Namespaces::new(backend.clone(), &[])),
+            events: Arc::new(EventHub::new(backend.clone())),
+            backend,
+            ring: None,
+            breakers: Vec::new(),
+            legacy_values: LegacyValues::default(),
+            auth: None,
+            acl: None,
+            invalidations: Arc::default(),
+        }
+    }
+}
+
+/// Path of the routes addressing one key.
+#[derive(Debug, Deserialize)]
+pub struct KeyPath {
+    pub key: String,
+}
+
+/// Query of `GET /events`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EventParams {
+    /// Only report keys starting with this prefix.
+    pub prefix: Option<String>,
+}
+
+/// Path of `GET /invalidations/:id`.
+#[derive(Debug, Deserialize)]
+pub struct JobPath {
+    pub id: u64,
+}
+
+/// Builds the HTTP router serving the cache API.
+pub fn app(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .merge(cache_routes())
+        .nest("/ns/:namespace", cache_routes())
+        .route("/admin/stats", get(admin_stats))
+        .route("/admin/ring", get(admin_ring))
+        .route("/admin/circuit-breakers", get(admin_circuit_breakers))
+        .route("/admin/acl", get(admin_acl))
+        .route("/admin/acl/reload", post(reload_acl))
+        .route("/metrics", get(metrics::metrics_handler))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), auth::authenticate))
+        .layer(axum::middleware::from_fn(metrics::track_http))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(telemetry::http_span)
+                .on_response(telemetry::record_response),
+        )
+        .with_state(state)
+}
+
+/// Routes of the cache API, served for the `default` namespace at the root
+/// and for every namespace under `/ns/:namespace`.
+fn cache_routes() -> Router<AppState> {
+    Router::new()
+        .route("/cache", get(list_keys).post(set_cache).delete(delete_keys))
+        .route("/cache/:key", get(get_cache).put(put_cache).delete(delete_cache))
+        .route("/cache/:key/ttl", get(get_ttl).put(update_ttl).delete(persist_key))
+        .route("/cache/:key/incr", post(increment))
+        .route("/cache/:key/decr", post(decrement))
+        .route("/cache/batch/get", post(batch_get))
+        .route("/cache/batch/set", post(batch_set))
+        .route("/events", get(stream_events))
+        .route("/invalidations", get(list_invalidations).post(start_invalidation))
+        .route("/invalidations/:id", get(get_invalidation))
+}
+
+#[tracing::instrument(skip_all)]
+async fn health_check(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({
+        "status": "healthy",
+        "backend": state.backend.health().await,
+    }))
+}
+
+#[tracing::instrument(skip_all)]
+async fn admin_stats(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "backend": state.backend.stats() }))
+}
+
+#[tracing::instrument(skip_all)]
+async fn admin_ring(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    match &state.ring {
+        Some(ring) => Ok(Json(ring.ring_report().await)),
+        None => Err(ApiError::NotFound("keys are not sharded".into())),
+    }
+}
+
+#[tracing::instrument(skip_all)]
+async fn admin_circuit_breakers(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Json<Vec<serde_json::Value>> {
+    Json(state.breakers.iter().map(|breaker| breaker.snapshot()).collect())
+}
+
+fn configured_acl(state: &AppState) -> Result<&Acl, ApiError> {
+    state
+        .acl
+        .as_deref()
+        .ok_or_else(|| ApiError::NotFound("no ACL policy is configured".into()))
+}
+
+#[tracing::instrument(skip_all)]
+async fn admin_acl(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<acl::Policy>, ApiError> {
+    Ok(Json((*configured_acl(&state)?.policy()).clone()))
+}
+
+/// Reads the ACL policy file again, keeping the current policy if it is invalid.
+#[tracing::instrument(skip_all)]
+async fn reload_acl(
+    axum::extract::State(state): axum::extract::State<AppState>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let rules = configured_acl(&state)?
+        .reload()
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    tracing::info!("Reloaded {} ACL rules", rules);
+    Ok(Json(serde_json::json!({ "rules": rules })))
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %payload.key))]
+async fn set_cache(
+    ns: Namespace,
+    access: Access,
+    ApiJson(payload): ApiJson<CacheEntry>,
+) -> Result<Response, ApiError> {
+    access.check(Operation::Write, &ns, &payload.key)?;
+    let ttl = ns.write_ttl(payload.ttl.map(Duration::from_secs))?;
+    let condition = payload.condition.resolve().map_err(ApiError::BadRequest)?;
+    invalidation::check_tags(&payload.tags)?;
+
+    let value = value::encode_json(&payload.value);
+    ns.check_size(&value)?;
+    let response = store(&ns, &payload.key, &value, ttl, condition, &payload.tags).await?;
+    tracing::info!("Successfully cached value for key: {}", payload.key);
+    Ok(response)
+}
+
+/// Writes an encoded value, only if `condition` holds when one is given,
+/// and answers with the `ETag` of the value.
+async fn store(
+    ns: &Namespace,
+    key: &str,
+    value: &[u8],
+    ttl: Option<Duration>,
+    condition: Option<SetCondition>,
+    tags: &[String],
+) -> Result<Response, ApiError> {
+    // Tagged before it is written so that an invalidation running meanwhile
+    // cannot miss the key.
+    if !tags.is_empty() {
+        let tags: Vec<&str> = tags.iter().map(String::as_str).collect();
+        ns.backend.tag(key, &tags).await?;
+    }
+    if let Some(condition) = condition {
+        if !ns.backend.set_if(key, value, ttl, &condition).await? {
+            tracing::info!("Not caching key {}: {:?} does not hold", key, condition);
+            return Err(condition::rejected(key.to_string(), &condition));
+        }
+    } else {
+        ns.backend.set(key, value, ttl).await?;
+    }
+    Ok([(header::ETAG, condition::etag(value))].into_response())
+}
+
+/// Serves a value with its `ETag` and caching headers, answering
+/// `If-None-Match` / `If-Match` so copies held downstream can be revalidated.
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn get_cache(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    access.check(Operation::Read, &ns, &key)?;
+    let hit = ns.backend.lookup(&key).await?;
+    let etag = hit.as_ref().map(|hit| condition::etag(&hit.value));
+    let precondition = condition::check_read(&headers, etag.as_deref());
+    if precondition == ReadCondition::Failed {
+        return Err(ApiError::PreconditionFailed(format!("key {:?} does not match If-Match", key)));
+    }
+    let (Some(hit), Some(etag)) = (hit, etag) else {
+        tracing::info!("Key not found: {}", key);
+        return Ok(([(X_CACHE, "MISS")], ApiError::Miss(key)).into_response());
+    };
+    let headers = cache_headers(&hit, etag);
+    if precondition == ReadCondition::NotModified {
+        tracing::info!("Value for key {} not modified", key);
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+    let response = match value::decode(hit.value) {
+        StoredValue::Json(body) => {
+            (headers, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+        }
+        StoredValue::Raw { content_type, body } => {
+            (headers, [(header::CONTENT_TYPE, content_type)], body).into_response()
+        }
+        legacy @ StoredValue::Legacy(_) => {
+            (headers, Json(legacy.into_json(state.legacy_values)?)).into_response()
+        }
+    };
+    tracing::info!("Retrieved value for key: {}", key);
+    Ok(response)
+}
+
+/// `X-Cache` header telling whether a read found its key.
+const X_CACHE: header::HeaderName = header::HeaderName::from_static("x-cache");
+
+/// Caching headers of a hit: `max-age` is the remaining TTL, and keys that
+/// never expire must be revalidated.
+fn cache_headers(hit: &Hit, etag: String) -> HeaderMap {
+    let cache_control = match hit.ttl {
+        KeyTtl::Expires(remaining) => format!("max-age={}", remaining.as_secs()),
+        _ => "no-cache".to_string(),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(X_CACHE, header::HeaderValue::from_static("HIT"));
+    headers.insert(header::CACHE_CONTROL, cache_control.parse().expect("valid header value"));
+    headers.insert(header::AGE, hit.age.as_secs().into());
+    headers.insert(header::ETAG, etag.parse().expect("valid header value"));
+    headers
+}
+
+/// Stores the request body as is, to be served back with its `Content-Type`.
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn put_cache(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+    ApiQuery(params): ApiQuery<RawEntryParams>,
+    headers: HeaderMap,
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Response, ApiError> {
+    access.check(Operation::Write, &ns, &key)?;
+    let body = body?;
+    let content_type = match headers.get(header::CONTENT_TYPE) {
+        None => "application/octet-stream",
+        Some(content_type) => match content_type.to_str() {
+            Ok(content_type) if content_type.len() <= u16::MAX as usize => content_type,
+            _ => return Err(ApiError::BadRequest("invalid Content-Type".into())),
+        },
+    };
+    let ttl = ns.write_ttl(params.ttl.map(Duration::from_secs))?;
+    let condition = condition::from_headers(&headers).map_err(ApiError::BadRequest)?;
+    let tags = params.tags();
+    invalidation::check_tags(&tags)?;
+
+    let value = value::encode_raw(content_type, &body);
+    ns.check_size(&value)?;
+    let response = store(&ns, &key, &value, ttl, condition, &tags).await?;
+    tracing::info!(
+        "Successfully cached {} bytes of {} for key: {}",
+        body.len(),
+        content_type,
+        key
+    );
+    Ok(response)
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn delete_cache(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+) -> Result<StatusCode, ApiError> {
+    access.check(Operation::Delete, &ns, &key)?;
+    if ns.backend.delete(&key).await? {
+        tracing::info!("Deleted key: {}", key);
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        tracing::info!("Key not found: {}", key);
+        Err(ApiError::Miss(key))
+    }
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn get_ttl(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+) -> Result<Json<TtlReport>, ApiError> {
+    access.check(Operation::Read, &ns, &key)?;
+    match ns.backend.ttl(&key).await? {
+        KeyTtl::Missing => Err(ApiError::Miss(key)),
+        ttl => Ok(Json(TtlReport::new(key, ttl, SystemTime::now()))),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn update_ttl(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+    ApiJson(update): ApiJson<TtlUpdate>,
+) -> Result<Json<TtlReport>, ApiError> {
+    access.check(Operation::Write, &ns, &key)?;
+    let now = SystemTime::now();
+    let ttl = update.remaining(now).map_err(ApiError::BadRequest)?;
+    ns.check_ttl(Some(ttl))?;
+    if !ns.backend.set_ttl(&key, Some(ttl)).await? {
+        return Err(ApiError::Miss(key));
+    }
+    tracing::info!("Set TTL of key {} to {:?}", key, ttl);
+    Ok(Json(TtlReport::new(key, KeyTtl::Expires(ttl), now)))
+}
+
+/// Removes the expiry of a key.
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn persist_key(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+) -> Result<StatusCode, ApiError> {
+    access.check(Operation::Write, &ns, &key)?;
+    ns.check_ttl(None)?;
+    if !ns.backend.set_ttl(&key, None).await? {
+        return Err(ApiError::Miss(key));
+    }
+    tracing::info!("Removed TTL of key: {}", key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn increment(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+    ApiJson(update): ApiJson<CounterUpdate>,
+) -> Result<Json<CounterValue>, ApiError> {
+    update_counter(ns, access, key, update.by, update.ttl).await
+}
+
+#[tracing::instrument(skip_all, fields(cache.key = %key))]
+async fn decrement(
+    ns: Namespace,
+    access: Access,
+    Path(KeyPath { key }): Path<KeyPath>,
+    ApiJson(update): ApiJson<CounterUpdate>,
+) -> Result<Json<CounterValue>, ApiError> {
+    let by = update
+        .by
+        .checked_neg()
+        .ok_or_else(|| ApiError::BadRequest(format!("cannot decrement by {}", update.by)))?;
+    update_counter(ns, access, key, by, update.ttl).await
+}
+
+/// Adds `by` to the counter under `key`, which expires after `ttl` seconds
+/// (or the namespace default) if the update creates it.
+async fn update_counter(
+    ns: Namespace,
+    access: Access,
+    key: String,
+    by: Counter,
+    ttl: Option<u64>,
+) -> Result<Json<CounterValue>, ApiError> {
+    access.check(Operation::Write, &ns, &key)?;
+    let ttl = ns.write_ttl(ttl.map(Duration::from_secs))?;
+    let value = match ns.backend.increment(&key, by, ttl).await {
+        Ok(value) => value,
+        Err(BackendError::NotCounter(_)) => return Err(ApiError::NotCounter(key)),
+        Err(e) => return Err(e.into()),
+    };
+    tracing::info!("Counter {} is now {}", key, value);
+    Ok(Json(CounterValue { key, value }))
+}
+
+#[tracing::instrument(skip_all)]
+async fn list_keys(
+    ns: Namespace,
+    access: Access,
+    ApiQuery(params): ApiQuery<ListKeysParams>,
+) -> Result<Json<KeyListing>, ApiError> {
+    let pattern = match (params.prefix, params.pattern) {
+        (Some(_), Some(_)) => {
+            return Err(ApiError::BadRequest("prefix and match are exclusive".into()))
+        }
+        (Some(prefix), None) => format!("{}*", backend::escape_glob(&prefix)),
+        (None, Some(pattern)) => pattern,
+        (None, None) => "*".to_string(),
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let mut cursor = params.cursor.filter(|cursor| !cursor.is_empty());
+    let mut keys = Vec::new();
+    for _ in 0..MAX_SCAN_ROUNDS {
+        let page = ns.backend.scan(cursor.as_deref(), &pattern, limit - keys.len()).await?;
+        // Keys the caller may not read are left out rather than refused.
+        keys.extend(
+            page.keys
+                .into_iter()
+                .filter(|key| access.check(Operation::Read, &ns, key).is_ok()),
+        );
+        cursor = page.cursor;
+        if cursor.is_none() || keys.len() >= limit {
+            break;
+        }
+    }
+
+    let keys: Vec<ListedKey> = if params.ttl || params.size {
+        let names: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let infos = ns.backend.inspect_many(&names).await?;
+        // Keys that expired since they were scanned are dropped.
+        keys.into_iter()
+            .zip(infos)
+            .filter_map(|(key, info)| {
+                let info = info?;
+                let ttl_ms = match info.ttl {
+                    KeyTtl::Missing => return None,
+                    KeyTtl::Persistent => None,
+                    KeyTtl::Expires(remaining) => Some(remaining.as_millis() as u64),
+                };
+                Some(ListedKey {
+                    key,
+                    ttl_ms: params.ttl.then_some(ttl_ms),
+                    size: params.size.then_some(info.size),
+                })
+            })
+            .collect()
+    } else {
+        keys.into_iter()
+            .map(|key| ListedKey { key, ttl_ms: None, size: None })
+            .collect()
+    };
+    tracing::info!("Listed {} keys matching {:?}", keys.len(), pattern);
+    Ok(Json(KeyListing { keys, cursor }))
+}
+
+#[tracing::instrument(skip_all, fields(cache.keys = payload.keys.len()))]
+async fn delete_keys(
+    ns: Namespace,
+    access: Access,
+    ApiJson(payload): ApiJson<DeleteKeys>,
+) -> Result<Json<DeleteReport>, ApiError> {
+    for key in &payload.keys {
+        access.check(Operation::Delete, &ns, key)?;
+    }
+    let mut report = DeleteReport::default();
+    for key in payload.keys {
+        if ns.backend.delete(&key).await? {
+            report.deleted.push(key);
+        } else {
+            report.missing.push(key);
+        }
+    }
+    tracing::info!(
+        "Deleted {} keys, {} not found",
+        report.deleted.len(),
+        report.missing.len()
+    );
+    Ok(Json(report))
+}
+
+#[tracing::instrument(skip_all, fields(cache.keys = payload.keys.len()))]
+async fn batch_get(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+    ApiJson(payload): ApiJson<BatchGet>,
+) -> Result<Json<BatchGetReport>, ApiError> {
+    for key in &payload.keys {
+        access.check(Operation::Read, &ns, key)?;
+    }
+    let keys: Vec<&str> = payload.keys.iter().map(String::as_str).collect();
+    let found = ns.backend.get_many_with_ttl(&keys).await?;
+
+    let mut report = BatchGetReport::default();
+    for (key, entry) in payload.keys.into_iter().zip(found) {
+        match entry {
+            Some((value, _)) => match value::decode(value).into_json(state.legacy_values) {
+                Ok(value) => {
+                    report.values.insert(key, value);
+                }
+                Err(e) => {
+                    let reason = e.to_string();
+                    let error = ApiError::NotRepresentable { key: key.clone(), reason };
+                    report.errors.insert(key, error.body());
+                }
+            },
+            None => report.misses.push(key),
+        }
+    }
+    tracing::info!(
+        "Retrieved {} keys, {} not found",
+        report.values.len(),
+        report.misses.len()
+    );
+    Ok(Json(report))
+}
+
+#[tracing::instrument(skip_all, fields(cache.keys = payload.entries.len()))]
+async fn batch_set(
+    ns: Namespace,
+    access: Access,
+    ApiJson(payload): ApiJson<BatchSet>,
+) -> Result<Json<BatchSetReport>, ApiError> {
+    let conditions = payload
+        .entries
+        .iter()
+        .map(|entry| entry.condition.resolve())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ApiError::BadRequest)?;
+    let values: Vec<Vec<u8>> = payload
+        .entries
+        .iter()
+        .map(|entry| value::encode_json(&entry.value))
+        .collect();
+    // Entries the caller may not write or breaking the namespace limits
+    // fail on their own.
+    let ttls: Vec<Result<Option<Duration>, ApiError>> = payload
+        .entries
+        .iter()
+        .zip(&values)
+        .map(|(entry, value)| {
+            access.check(Operation::Write, &ns, &entry.key)?;
+            invalidation::check_tags(&entry.tags)?;
+            ns.check_size(value)?;
+            ns.write_ttl(entry.ttl.map(Duration::from_secs))
+        })
+        .collect();
+    for (entry, ttl) in payload.entries.iter().zip(&ttls) {
+        if !entry.tags.is_empty() && ttl.is_ok() {
+            let tags: Vec<&str> = entry.tags.iter().map(String::as_str).collect();
+            ns.backend.tag(&entry.key, &tags).await?;
+        }
+    }
+    // Unconditional entries share one pipeline; conditional ones are
+    // checked and written one by one.
+    let items: Vec<SetItem> = payload
+        .entries
+        .iter()
+        .zip(&values)
+        .zip(&conditions)
+        .zip(&ttls)
+        .filter_map(|(((entry, value), condition), ttl)| match (condition, ttl) {
+            (None, Ok(ttl)) => Some(SetItem {
+                key: &entry.key,
+                value,
+                ttl: *ttl,
+            }),
+            _ => None,
+        })
+        .collect();
+    let mut pipelined = ns.backend.set_many(&items).await?.into_iter();
+
+    let mut results = Vec::with_capacity(payload.entries.len());
+    let entries = payload.entries.into_iter().zip(&values).zip(&conditions).zip(ttls);
+    for (((entry, value), condition), ttl) in entries {
+        let result = match (condition, ttl) {
+            (_, Err(e)) => Err(e),
+            (None, Ok(_)) => pipelined.next().expect("one result per item").map_err(ApiError::from),
+            (Some(condition), Ok(ttl)) => {
+                match ns.backend.set_if(&entry.key, value, ttl, condition).await {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(condition::rejected(entry.key.clone(), condition)),
+                    Err(e) => Err(ApiError::from(e)),
+                }
+            }
+        };
+        results.push(SetStatus {
+            key: entry.key,
+            ok: result.is_ok(),
+            error: result.err().map(|e| e.body()),
+        });
+    }
+    tracing::info!(
+        "Cached {} of {} values",
+        results.iter().filter(|status| status.ok).count(),
+        results.len()
+    );
+    Ok(Json(BatchSetReport { results }))
+}
+
+/// Starts removing every key of the namespace carrying a tag or starting
+/// with a prefix, answering with the job to poll for its progress.
+#[tracing::instrument(skip_all)]
+async fn start_invalidation(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+    ApiJson(target): ApiJson<Target>,
+) -> Result<Response, ApiError> {
+    let location = match ns.name() {
+        namespace::DEFAULT_NAMESPACE => String::new(),
+        name => format!("/ns/{}", name),
+    };
+    let job = state.invalidations.start(ns, access, target);
+    tracing::info!("Started invalidation {} of {:?}", job.id, job.target);
+    let location = format!("{}/invalidations/{}", location, job.id);
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, location)],
+        Json(job.report()),
+    )
+        .into_response())
+}
+
+/// Reports an invalidation of the namespace to the caller that started it,
+/// or to admins.
+async fn get_invalidation(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+    Path(JobPath { id }): Path<JobPath>,
+) -> Result<Json<JobReport>, ApiError> {
+    state
+        .invalidations
+        .get(id)
+        .filter(|job| job.namespace == ns.name())
+        .filter(|job| access.may_view(job.started_by.as_deref()))
+        .map(|job| Json(job.report()))
+        .ok_or_else(|| ApiError::NotFound(format!("invalidation {} not found", id)))
+}
+
+/// Lists the invalidations of the namespace the caller may see.
+async fn list_invalidations(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+) -> Json<Vec<JobReport>> {
+    let jobs = state.invalidations.list(ns.name());
+    Json(
+        jobs.iter()
+            .filter(|job| access.may_view(job.started_by.as_deref()))
+            .map(|job| job.report())
+            .collect(),
+    )
+}
+
+/// Streams changes to the keys of the namespace as server-sent events.
+async fn stream_events(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    ns: Namespace,
+    access: Access,
+    ApiQuery(params): ApiQuery<EventParams>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    tracing::info!("Streaming key events of namespace {}", ns.name());
+    let prefix = params.prefix.unwrap_or_default();
+    Sse::new(state.events.stream(ns, access, prefix)).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request;
+    use backend::{MemoryBackend, MemoryLimits};
+    use tower::ServiceExt;
+
+    fn state() -> AppState {
+        AppState::new(Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 100,
+            max_bytes: 64 * 1024,
+        })))
+    }
+
+    /// Sends `request` to the app, returning the response with its body.
+    async fn send(app: &Router, request: Request<Body>) -> (Response, Bytes) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (Response::from_parts(parts, Body::empty()), body)
+    }
+
+    fn json(method: &str, uri: &str, body: serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    fn empty(method: &str, uri: &str) -> Request<Body> {
+        Request::builder().method(method).uri(uri).body(Body::empty()).unwrap()
+    }
+
+    fn parse(body: &[u8]) -> serde_json::Value {
+        serde_json::from_slice(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_get_reports_binary_values_per_key() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": {"n": 1}}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        let put = Request::put("/cache/png")
+            .header(header::CONTENT_TYPE, "image/png")
+            .body(Body::from(vec![0x89, 0x50, 0xff, 0xfe]))
+            .unwrap();
+        assert_eq!(send(&app, put).await.0.status(), StatusCode::OK);
+
+        let get = json("POST", "/cache/batch/get", serde_json::json!({"keys": ["a", "png", "b"]}));
+        let (response, body) = send(&app, get).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = parse(&body);
+        assert_eq!(report["values"], serde_json::json!({"a": {"n": 1}}));
+        assert_eq!(report["misses"], serde_json::json!(["b"]));
+        assert_eq!(report["errors"]["png"]["code"], "not_representable");
+    }
+
+    #[tokio::test]
+    async fn zero_ttls_are_rejected() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": 1, "ttl": 0}));
+        let (response, body) = send(&app, set).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(parse(&body)["error"]["code"], "invalid_ttl");
+
+        let put = Request::put("/cache/a?ttl=0").body(Body::from("x")).unwrap();
+        assert_eq!(send(&app, put).await.0.status(), StatusCode::BAD_REQUEST);
+        let get = Request::get("/cache/a").body(Body::empty()).unwrap();
+        assert_eq!(send(&app, get).await.0.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn counters_read_back_as_strings() {
+        let app = app(state());
+        let incr = json("POST", "/cache/n/incr", serde_json::json!({"by": 5}));
+        let (response, body) = send(&app, incr).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)["value"], 5);
+
+        let get = Request::get("/cache/n").body(Body::empty()).unwrap();
+        let (response, body) = send(&app, get).await;
+        assert_eq!(parse(&body), "5");
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        // `if_value` compares JSON-encoded values, which a counter never is.
+        let by_value = json(
+            "POST",
+            "/cache",
+            serde_json::json!({"key": "n", "value": 0, "if_value": "5"}),
+        );
+        assert_eq!(send(&app, by_value).await.0.status(), StatusCode::PRECONDITION_FAILED);
+        let by_version = json(
+            "POST",
+            "/cache",
+            serde_json::json!({"key": "n", "value": 0, "if_version": etag}),
+        );
+        assert_eq!(send(&app, by_version).await.0.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn serves_the_cache_without_redis() {
+        let app = app(state());
+        let (response, body) = send(&app, empty("GET", "/health")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)["status"], "healthy");
+
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": "1"}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        let (response, body) = send(&app, empty("GET", "/cache/a")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body), "1");
+    }
+
+    #[tokio::test]
+    async fn admin_stats_report_the_tiers() {
+        let limits = MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        };
+        let l2 = Arc::new(MemoryBackend::new(limits));
+        let tiered = backend::TieredBackend::new(l2, limits, Duration::from_secs(60));
+        let app = app(AppState::new(Arc::new(tiered)));
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": 1}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        for _ in 0..2 {
+            assert_eq!(send(&app, empty("GET", "/cache/a")).await.0.status(), StatusCode::OK);
+        }
+
+        let (response, body) = send(&app, empty("GET", "/admin/stats")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let stats = &parse(&body)["backend"];
+        assert_eq!(stats["l1"]["hits"], 1);
+        assert_eq!(stats["l1"]["misses"], 1);
+        assert_eq!(stats["l2"]["hits"], 1);
+    }
+
+    #[tokio::test]
+    async fn admin_ring_reports_shard_ownership() {
+        let (response, body) = send(&app(state()), empty("GET", "/admin/ring")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(parse(&body)["error"]["code"], "not_found");
+
+        let shard = || {
+            Arc::new(MemoryBackend::new(MemoryLimits {
+                max_entries: 100,
+                max_bytes: 4096,
+            })) as Arc<dyn CacheBackend>
+        };
+        let shards = vec![("a".to_string(), shard()), ("b".to_string(), shard())];
+        let ring = Arc::new(backend::ShardedBackend::new(shards, 160));
+        let mut state = AppState::new(ring.clone());
+        state.ring = Some(ring);
+        let app = app(state);
+        for i in 0..10 {
+            let set = json("POST", "/cache", serde_json::json!({"key": i.to_string(), "value": i}));
+            assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        }
+
+        let (response, body) = send(&app, empty("GET", "/admin/ring")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = parse(&body);
+        let shards = report["shards"].as_array().unwrap();
+        assert_eq!(shards.len(), 2);
+        assert_eq!(shards[0]["name"], "a");
+        assert_eq!(shards[0]["virtual_nodes"], 160);
+        let keys: u64 = shards.iter().map(|shard| shard["keys"].as_u64().unwrap()).sum();
+        assert_eq!(keys, 10);
+    }
+
+    #[tokio::test]
+    async fn admin_circuit_breakers_list_their_state() {
+        let breaker = Arc::new(CircuitBreaker::new(
+            "redis",
+            circuit_breaker::BreakerSettings {
+                failure_threshold: 1,
+                reset_timeout: Duration::from_secs(30),
+                half_open_max_requests: 1,
+            },
+        ));
+        let mut state = state();
+        state.breakers = vec![breaker.clone()];
+        let app = app(state);
+
+        let (response, body) = send(&app, empty("GET", "/admin/circuit-breakers")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)[0]["name"], "redis");
+        assert_eq!(parse(&body)[0]["state"], "closed");
+
+        breaker.try_acquire().unwrap().record(false);
+        let (_, body) = send(&app, empty("GET", "/admin/circuit-breakers")).await;
+        let snapshot = &parse(&body)[0];
+        assert_eq!(snapshot["state"], "open");
+        assert_eq!(snapshot["consecutive_failures"], 1);
+        assert!(snapshot["retry_after_secs"].as_u64().unwrap() <= 30);
+    }
+
+    #[tokio::test]
+    async fn metrics_count_requests_by_route() {
+        let app = app(state());
+        let (response, _) = send(&app, empty("GET", "/cache/metrics:absent")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let (response, body) = send(&app, empty("GET", "/metrics")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap();
+        assert!(content_type.starts_with("text/plain"));
+        let metrics = std::str::from_utf8(&body).unwrap();
+        let counted = r#"http_requests_total{endpoint="/cache/:key",method="GET",status="404"}"#;
+        assert!(metrics.contains(counted), "{}", metrics);
+    }
+
+    #[tokio::test]
+    async fn deletes_report_whether_the_key_existed() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": "1"}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+
+        let (response, body) = send(&app, empty("DELETE", "/cache/a")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(body.is_empty());
+        let (response, body) = send(&app, empty("DELETE", "/cache/a")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(parse(&body)["error"]["code"], "cache_miss");
+    
+
+        let set = json("POST", "/cache", serde_json::json!({"key": "b", "value": "2"}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        let delete = json("DELETE", "/cache", serde_json::json!({"keys": ["a", "b"]}));
+        let (response, body) = send(&app, delete).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body), serde_json::json!({"deleted": ["b"], "missing": ["a"]}));
+    }
+
+    #[tokio::test]
+    async fn batches_report_each_entry() {
+        let app = app(state());
+        let entries = serde_json::json!({"entries": [
+            {"key": "a", "value": 1},
+            {"key": "b", "value": {"n": 2}, "ttl": 60},
+            {"key": "c", "value": 3, "ttl": 0},
+        ]});
+        let (response, body) = send(&app, json("POST", "/cache/batch/set", entries)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let results = parse(&body)["results"].clone();
+        assert_eq!(results[0], serde_json::json!({"key": "a", "ok": true}));
+        assert_eq!(results[1], serde_json::json!({"key": "b", "ok": true}));
+        assert_eq!(results[2]["ok"], false);
+        assert_eq!(results[2]["error"]["code"], "invalid_ttl");
+
+        let keys = serde_json::json!({"keys": ["a", "b", "c"]});
+        let (response, body) = send(&app, json("POST", "/cache/batch/get", keys)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let report = parse(&body);
+        assert_eq!(report["values"], serde_json::json!({"a": 1, "b": {"n": 2}}));
+        assert_eq!(report["misses"], serde_json::json!(["c"]));
+    }
+
+    #[tokio::test]
+    async fn raw_values_keep_their_content_type() {
+        let app = app(state());
+        let bytes = vec![0x89, 0x50, 0x4e, 0x47, 0x00, 0xff];
+        let put = Request::put("/cache/logo")
+            .header(header::CONTENT_TYPE, "image/png")
+            .body(Body::from(bytes.clone()))
+            .unwrap();
+        assert_eq!(send(&app, put).await.0.status(), StatusCode::OK);
+        let (response, body) = send(&app, empty("GET", "/cache/logo")).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+        assert_eq!(body.to_vec(), bytes);
+
+        let put = Request::put("/cache/blob").body(Body::from("abc")).unwrap();
+        assert_eq!(send(&app, put).await.0.status(), StatusCode::OK);
+        let (response, body) = send(&app, empty("GET", "/cache/blob")).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(&body[..], b"abc");
+    }
+
+    #[tokio::test]
+    async fn json_values_round_trip() {
+        let app = app(state());
+        let values = [
+            serde_json::json!([1, "x"]),
+            serde_json::json!({"user": {"id": 7, "roles": ["admin"]}}),
+            serde_json::json!("text"),
+            serde_json::json!(null),
+        ];
+        for value in values {
+            let set = json("POST", "/cache", serde_json::json!({"key": "doc", "value": value}));
+            assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+            let (response, body) = send(&app, empty("GET", "/cache/doc")).await;
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+            assert_eq!(parse(&body), value);
+        }
+    }
+
+    #[tokio::test]
+    async fn errors_carry_their_status_and_code() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": 1}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        let cases = [
+            (empty("GET", "/cache/missing"), StatusCode::NOT_FOUND, "cache_miss"),
+            (
+                json("POST", "/cache", serde_json::json!({"value": 1})),
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+            ),
+            (
+                json("POST", "/cache", serde_json::json!({"key": "a", "value": 1, "ttl": 0})),
+                StatusCode::BAD_REQUEST,
+                "invalid_ttl",
+            ),
+            (
+                Request::post("/cache")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{"))
+                    .unwrap(),
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+            ),
+        ];
+        for (request, status, code) in cases {
+            let uri = request.uri().clone();
+            let (response, body) = send(&app, request).await;
+            assert_eq!(response.status(), status, "{}", uri);
+            let body = parse(&body);
+            assert_eq!(body["error"]["code"], code, "{}", uri);
+            assert!(body["error"]["message"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn ttls_are_inspected_and_changed() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": 1, "ttl": 60}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+
+        let (response, body) = send(&app, empty("GET", "/cache/a/ttl")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let ttl_ms = parse(&body)["ttl_ms"].as_u64().unwrap();
+        assert!(ttl_ms > 59_000 && ttl_ms <= 60_000, "{}", ttl_ms);
+
+        let update = json("PUT", "/cache/a/ttl", serde_json::json!({"ttl": "2h"}));
+        let (response, body) = send(&app, update).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)["ttl"], "2h");
+
+        let (response, _) = send(&app, empty("DELETE", "/cache/a/ttl")).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let (_, body) = send(&app, empty("GET", "/cache/a/ttl")).await;
+        assert_eq!(parse(&body)["ttl_ms"], serde_json::Value::Null);
+
+        let (response, _) = send(&app, empty("GET", "/cache/missing/ttl")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let update = json("PUT", "/cache/a/ttl", serde_json::json!({"ttl": "2h", "ttl_ms": 5}));
+        let (response, _) = send(&app, update).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn conditional_writes_check_the_stored_value() {
+        let app = app(state());
+        let write = |body: serde_json::Value| json("POST", "/cache", body);
+        let (response, _) = send(&app, write(serde_json::json!({"key": "a", "value": 1}))).await;
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+
+        let cases = [
+            (serde_json::json!({"key": "a", "value": 2, "if_absent": true}), StatusCode::CONFLICT),
+            (
+                serde_json::json!({"key": "b", "value": 2, "if_present": true}),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (serde_json::json!({"key": "a", "value": 2, "if_value": 1}), StatusCode::OK),
+            (
+                serde_json::json!({"key": "a", "value": 3, "if_version": etag}),
+                StatusCode::PRECONDITION_FAILED,
+            ),
+            (serde_json::json!({"key": "b", "value": 1, "if_absent": true}), StatusCode::OK),
+        ];
+        for (body, status) in cases {
+            let (response, _) = send(&app, write(body.clone())).await;
+            assert_eq!(response.status(), status, "{}", body);
+        }
+        let (_, body) = send(&app, empty("GET", "/cache/a")).await;
+        assert_eq!(parse(&body), 2);
+
+        let put = |condition, etag: &str| {
+            Request::put("/cache/a").header(condition, etag).body(Body::from("raw")).unwrap()
+        };
+        let (response, _) = send(&app, put(header::IF_NONE_MATCH, "*")).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let (response, _) = send(&app, put(header::IF_MATCH, &etag)).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let (response, _) = send(&app, empty("GET", "/cache/a")).await;
+        let etag = response.headers()[header::ETAG].to_str().unwrap().to_string();
+        let (response, _) = send(&app, put(header::IF_MATCH, &etag)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn etags_revalidate_reads() {
+        let app = app(state());
+        let set = json("POST", "/cache", serde_json::json!({"key": "a", "value": 1}));
+        let (response, _) = send(&app, set).await;
+        let etag = response.headers()[header::ETAG].clone();
+
+        let (response, _) = send(&app, empty("GET", "/cache/a")).await;
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert_eq!(response.headers()["x-cache"], "HIT");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let (response, _) = send(&app, empty("GET", "/cache/missing")).await;
+        assert_eq!(response.headers()["x-cache"], "MISS");
+        let revalidate = Request::get("/cache/a")
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap();
+        let (response, body) = send(&app, revalidate).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+        assert!(body.is_empty());
+
+        let stale = Request::get("/cache/a")
+            .header(header::IF_NONE_MATCH, "\"other\"")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(&app, stale).await.0.status(), StatusCode::OK);
+        let mismatch = Request::get("/cache/a")
+            .header(header::IF_MATCH, "\"other\"")
+            .body(Body::empty())
+            .unwrap();
+        let (response, body) = send(&app, mismatch).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(parse(&body)["error"]["code"], "precondition_failed");
+    }
+
+    #[tokio::test]
+    async fn namespaces_keep_their_keys_apart() {
+        let mut state = state();
+        let mut sessions = namespace::NamespaceSettings::new("sessions");
+        sessions.default_ttl = Some(Duration::from_secs(60));
+        state.namespaces = Arc::new(Namespaces::new(state.backend.clone(), &[sessions]));
+        let app = app(state);
+        let root = json("POST", "/cache", serde_json::json!({"key": "a", "value": "root"}));
+        assert_eq!(send(&app, root).await.0.status(), StatusCode::OK);
+        let scoped = serde_json::json!({"key": "a", "value": "scoped"});
+        let scoped = json("POST", "/ns/sessions/cache", scoped);
+        assert_eq!(send(&app, scoped).await.0.status(), StatusCode::OK);
+
+        let (_, body) = send(&app, empty("GET", "/cache/a")).await;
+        assert_eq!(parse(&body), "root");
+        let (_, body) = send(&app, empty("GET", "/ns/default/cache/a")).await;
+        assert_eq!(parse(&body), "root");
+        let (_, body) = send(&app, empty("GET", "/ns/sessions/cache/a")).await;
+        assert_eq!(parse(&body), "scoped");
+        let (_, body) = send(&app, empty("GET", "/ns/sessions/cache/a/ttl")).await;
+        assert!(parse(&body)["ttl_ms"].as_u64().unwrap() <= 60_000);
+
+        let (response, body) = send(&app, empty("GET", "/ns/unknown/cache/a")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(parse(&body)["error"]["code"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn acls_restrict_callers_to_their_prefixes() {
+        let (response, _) = send(&app(state()), empty("GET", "/admin/acl")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let file = |name: &str, contents: &str| {
+            let name = format!("cache-router-{}-{}", std::process::id(), name);
+            let path = std::env::temp_dir().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let keys = file("keys", "gateway k-gateway\nops k-ops admin\n");
+        let policy = file(
+            "acl.json",
+            r#"{"rules": [
+                {"identity": "gateway", "prefixes": ["session:"], "operations": ["read", "write"]},
+                {"identity": "ops", "operations": ["admin"]}
+            ]}"#,
+        );
+        let settings = auth::AuthSettings {
+            api_keys_file: Some(keys.clone()),
+            ..Default::default()
+        };
+        let mut state = state();
+        state.auth = Some(Arc::new(Authenticator::load(&settings).unwrap().unwrap()));
+        state.acl = Some(Arc::new(Acl::load(&policy).unwrap()));
+        let app = app(state);
+        let as_caller = |key: &str, mut request: Request<Body>| {
+            request.headers_mut().insert("x-api-key", key.parse().unwrap());
+            request
+        };
+
+        let set = |key: &str| json("POST", "/cache", serde_json::json!({"key": key, "value": 1}));
+        let (response, _) = send(&app, as_caller("k-gateway", set("session:1"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let (response, body) = send(&app, as_caller("k-gateway", set("user:1"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let denied = &parse(&body)["error"]["denied"];
+        assert_eq!(denied["identity"], "gateway");
+        assert_eq!(denied["operation"], "write");
+        assert_eq!(denied["key"], "user:1");
+        let delete = empty("DELETE", "/cache/session:1");
+        let (response, _) = send(&app, as_caller("k-gateway", delete)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let (response, _) = send(&app, as_caller("k-gateway", empty("GET", "/admin/acl"))).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let (response, body) = send(&app, as_caller("k-ops", empty("GET", "/admin/acl"))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)["rules"].as_array().unwrap().len(), 2);
+        std::fs::remove_file(keys).unwrap();
+        std::fs::remove_file(policy).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keys_are_listed_page_by_page() {
+        let app = app(state());
+        for key in ["user:1", "user:2", "user:3", "user:*", "session:1"] {
+            let set = json("POST", "/cache", serde_json::json!({"key": key, "value": 1}));
+            assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        }
+
+        let mut listed = Vec::new();
+        let mut uri = "/cache?prefix=user:&limit=2".to_string();
+        loop {
+            let (response, body) = send(&app, empty("GET", &uri)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let page = parse(&body);
+            assert!(page["keys"].as_array().unwrap().len() <= 2);
+            for key in page["keys"].as_array().unwrap() {
+                listed.push(key["key"].as_str().unwrap().to_string());
+            }
+            match page["cursor"].as_str() {
+                Some(cursor) => uri = format!("/cache?prefix=user:&limit=2&cursor={}", cursor),
+                None => break,
+            }
+        }
+        listed.sort();
+        assert_eq!(listed, ["user:*", "user:1", "user:2", "user:3"]);
+
+        let (_, body) = send(&app, empty("GET", "/cache?match=user:%5B12%5D&size=true")).await;
+        let mut keys: Vec<_> = parse(&body)["keys"].as_array().unwrap().clone();
+        keys.sort_by_key(|key| key["key"].as_str().unwrap().to_string());
+        assert_eq!(keys[0]["key"], "user:1");
+        assert!(keys[0]["size"].is_u64());
+        assert_eq!(keys.len(), 2);
+
+        let (response, _) = send(&app, empty("GET", "/cache?prefix=a&match=b")).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn invalidations_remove_tagged_keys() {
+        let app = app(state());
+        let entries = [
+            serde_json::json!({"key": "product:1", "value": 1, "tags": ["catalog"]}),
+            serde_json::json!({"key": "product:2", "value": 2, "tags": ["catalog", "sale"]}),
+            serde_json::json!({"key": "user:1", "value": 3}),
+        ];
+        for entry in entries {
+            assert_eq!(send(&app, json("POST", "/cache", entry)).await.0.status(), StatusCode::OK);
+        }
+
+        let start = json("POST", "/invalidations", serde_json::json!({"tag": "catalog"}));
+        let (response, body) = send(&app, start).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let id = parse(&body)["id"].as_u64().unwrap();
+        let location = response.headers()[header::LOCATION].to_str().unwrap().to_string();
+        assert_eq!(location, format!("/invalidations/{}", id));
+
+        let mut report = serde_json::Value::Null;
+        for _ in 0..100 {
+            report = parse(&send(&app, empty("GET", &location)).await.1);
+            if report["status"] != "running" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(report["status"], "completed");
+        assert_eq!(report["tag"], "catalog");
+        assert_eq!(report["deleted"], 2);
+        let (_, body) = send(&app, empty("GET", "/invalidations")).await;
+        assert_eq!(parse(&body)[0]["id"], id);
+
+        for (key, status) in [
+            ("product:1", StatusCode::NOT_FOUND),
+            ("product:2", StatusCode::NOT_FOUND),
+            ("user:1", StatusCode::OK),
+        ] {
+            let (response, _) = send(&app, empty("GET", &format!("/cache/{}", key))).await;
+            assert_eq!(response.status(), status, "{}", key);
+        }
+        let (response, _) = send(&app, empty("GET", "/invalidations/999")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn events_stream_key_changes() {
+        use futures::StreamExt;
+
+        let app = app(state());
+        let response = app.clone().oneshot(empty("GET", "/events?prefix=user:")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/event-stream");
+        let mut frames = response.into_body().into_data_stream();
+
+        // The stream subscribes to the backend in the background, so keep
+        // writing until the first change gets through.
+        let mut frame = None;
+        for _ in 0..100 {
+            for key in ["session:1", "user:1"] {
+                let set = json("POST", "/cache", serde_json::json!({"key": key, "value": 1}));
+                assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+            }
+            let next = tokio::time::timeout(Duration::from_millis(50), frames.next()).await;
+            if let Ok(next) = next {
+                frame = Some(next.unwrap().unwrap());
+                break;
+            }
+        }
+        let frame = frame.expect("no event was streamed");
+        let frame = std::str::from_utf8(&frame).unwrap();
+        assert!(frame.contains("event: set\n"), "{}", frame);
+        assert!(frame.contains(r#"data: {"type":"set","key":"user:1"}"#), "{}", frame);
+    }
+
+    #[tokio::test]
+    async fn counters_increment_and_decrement() {
+        let app = app(state());
+        let update = |op: &str, body| json("POST", &format!("/cache/n/{}", op), body);
+        let (_, body) = send(&app, update("incr", serde_json::json!({}))).await;
+        assert_eq!(parse(&body), serde_json::json!({"key": "n", "value": 1}));
+        let (_, body) = send(&app, update("decr", serde_json::json!({"by": 3}))).await;
+        assert_eq!(parse(&body)["value"], -2);
+        let (_, body) = send(&app, update("incr", serde_json::json!({"by": 0.5}))).await;
+        assert_eq!(parse(&body)["value"], -1.5);
+
+        let (response, body) = send(&app, update("incr", serde_json::json!({}))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(parse(&body)["value"], -0.5);
+    
+
+        let set = json("POST", "/cache", serde_json::json!({"key": "doc", "value": 1}));
+        assert_eq!(send(&app, set).await.0.status(), StatusCode::OK);
+        let incr = json("POST", "/cache/doc/incr", serde_json::json!({}));
+        let (response, body) = send(&app, incr).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert_eq!(parse(&body)["error"]["code"], "not_a_counter");
+    }
+}
@@ -1,120 +1,193 @@
-use axum::{
-    routing::{get, post},
-    Router,
-    Json,
-    http::StatusCode,
-    extract::Path,
-};
-use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use redis::{Client, AsyncCommands, RedisError};
+use redis::Client;
 use std::sync::Arc;
-use axum::serve;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct CacheEntry {
-    key: String,
-    value: String,
-    ttl: Option<u64>,
-}
 
-#[derive(Clone)]
-struct AppState {
-    redis_client: Arc<Client>,
-}
+use cache_service::{app, AppState};
+use cache_service::acl::Acl;
+use cache_service::auth::Authenticator;
+use cache_service::backend::{
+    BreakerBackend, CacheBackend, ClusterBackend, InstrumentedBackend, MemoryBackend, RedisBackend,
+    SentinelBackend, ShardedBackend, TieredBackend,
+};
+use cache_service::circuit_breaker::CircuitBreaker;
+use cache_service::config::{BackendKind, Config};
+use cache_service::namespace::Namespaces;
+use cache_service::telemetry;
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let config = Config::from_env().expect("Invalid configuration");
 
-    // Initialize Redis client
-    let redis_url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://redis:6379".into());
-    let redis_client = Client::open(redis_url).expect("Failed to create Redis client");
-    let redis_client = Arc::new(redis_client);
-    
-    let state = AppState { redis_client };
+    // Initialize tracing
+    let tracer_provider = telemetry::init(&config.tracing).expect("Failed to initialize tracing");
+    let state = build_state(&config);
+    #[cfg(unix)]
+    if let Some(acl) = &state.acl {
+        reload_on_hangup(acl.clone());
+    }
 
     // Build our application with a route
-    let app = Router::new()
-        .route("/health", get(health_check))
-        .route("/cache", post(set_cache))
-        .route("/cache/:key", get(get_cache))
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    let app = app(state);
 
     // Run it
     let addr = SocketAddr::from(([0, 0, 0, 0], 8081));
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     tracing::info!("listening on {}", addr);
-    axum::serve(listener, app).await.unwrap();
-}
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
 
-async fn health_check() -> StatusCode {
-    StatusCode::OK
+    // Flush spans still buffered by the exporter
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("Failed to flush traces: {}", e);
+        }
+    }
 }
 
-async fn set_cache(
-    axum::extract::State(state): axum::extract::State<AppState>,
-    Json(payload): Json<CacheEntry>,
-) -> StatusCode {
-    let mut conn = match state.redis_client.get_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            tracing::error!("Failed to connect to Redis: {}", e);
-            return StatusCode::INTERNAL_SERVER_ERROR;
-        }
+async fn shutdown_signal() {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
     };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    let result = match payload.ttl {
-        Some(ttl) => conn.set_ex::<_, _, ()>(&payload.key, &payload.value, ttl).await,
-        None => conn.set::<_, _, ()>(&payload.key, &payload.value).await,
-    };
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("Shutting down");
+}
 
-    match result {
-        Ok(_) => {
-            tracing::info!("Successfully cached value for key: {}", payload.key);
-            StatusCode::OK
-        }
-        Err(e) => {
-            tracing::error!("Failed to set cache: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+/// Reloads the ACL policy whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+fn reload_on_hangup(acl: Arc<Acl>) {
+    tokio::spawn(async move {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .expect("Failed to install SIGHUP handler");
+        while hangup.recv().await.is_some() {
+            match acl.reload() {
+                Ok(rules) => tracing::info!("Reloaded {} ACL rules", rules),
+                Err(e) => tracing::error!("Keeping the current ACL policy: {}", e),
+            }
         }
-    }
+    });
 }
 
-async fn get_cache(
-    axum::extract::State(state): axum::extract::State<AppState>,
-    Path(key): Path<String>,
-) -> Result<Json<String>, StatusCode> {
-    let mut conn = match state.redis_client.get_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            tracing::error!("Failed to connect to Redis: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+fn build_state(config: &Config) -> AppState {
+    let mut ring = None;
+    let mut breakers = Vec::new();
+    let mut guard = |name: &str, backend: Arc<dyn CacheBackend>| -> Arc<dyn CacheBackend> {
+        if !config.breaker_enabled {
+            return backend;
         }
+        let breaker = Arc::new(CircuitBreaker::new(name, config.breaker));
+        breakers.push(breaker.clone());
+        Arc::new(BreakerBackend::new(backend, breaker))
     };
-
-    match conn.get::<_, String>(&key).await {
-        Ok(value) => {
-            tracing::info!("Retrieved value for key: {}", key);
-            Ok(Json(value))
+    let backend: Arc<dyn CacheBackend> = match config.backend {
+        BackendKind::Redis => {
+            let redis_client =
+                Client::open(config.redis_url.as_str()).expect("Failed to create Redis client");
+            tracing::info!("Using Redis backend");
+            guard("redis", Arc::new(RedisBackend::new(redis_client, config.redis_connection)))
         }
-        Err(e) => {
-            if e.to_string().contains("no such key") {
-                tracing::info!("Key not found: {}", key);
-                Err(StatusCode::NOT_FOUND)
-            } else {
-                tracing::error!("Failed to get cache: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
+        BackendKind::Cluster => {
+            let backend =
+                ClusterBackend::cluster(&config.redis_cluster_nodes, config.redis_connection)
+                    .expect("Failed to create Redis Cluster client");
+            tracing::info!(
+                "Using Redis Cluster backend with {} seed nodes",
+                config.redis_cluster_nodes.len()
+            );
+            guard("redis-cluster", Arc::new(backend))
+        }
+        BackendKind::Sentinel => {
+            let backend = SentinelBackend::sentinel(
+                &config.redis_sentinels,
+                &config.redis_sentinel_service,
+                config.redis_sentinel_check_interval,
+                config.redis_connection,
+            )
+            .expect("Failed to create Redis Sentinel client");
+            tracing::info!(
+                "Using Redis Sentinel backend for {} via {} sentinels",
+                config.redis_sentinel_service,
+                config.redis_sentinels.len()
+            );
+            guard("redis-sentinel", Arc::new(backend))
+        }
+        BackendKind::Sharded => {
+            assert!(!config.redis_shards.is_empty(), "REDIS_SHARDS must not be empty");
+            let shards = config
+                .redis_shards
+                .iter()
+                .map(|url| {
+                    // Shards are named by address so credentials stay out of the ring.
+                    let client = Client::open(url.as_str()).expect("Failed to create Redis client");
+                    let name = client.get_connection_info().addr.to_string();
+                    let shard = guard(
+                        &name,
+                        Arc::new(RedisBackend::new(client, config.redis_connection)),
+                    );
+                    (name, shard)
+                })
+                .collect();
+            let sharded = Arc::new(ShardedBackend::new(shards, config.shard_virtual_nodes));
+            tracing::info!(
+                "Using sharded Redis backend over {} instances",
+                config.redis_shards.len()
+            );
+            ring = Some(sharded.clone());
+            sharded
+        }
+        BackendKind::Memory => {
+            tracing::info!(
+                "Using in-memory backend (max {} entries, {} bytes)",
+                config.memory.max_entries,
+                config.memory.max_bytes
+            );
+            Arc::new(MemoryBackend::new(config.memory))
         }
+    };
+
+    let backend = if config.l1_enabled && config.backend != BackendKind::Memory {
+        tracing::info!(
+            "Using L1 cache (max {} entries, {} bytes, ttl {:?})",
+            config.l1.max_entries,
+            config.l1.max_bytes,
+            config.l1_max_ttl
+        );
+        Arc::new(TieredBackend::new(backend, config.l1, config.l1_max_ttl))
+    } else {
+        backend
+    };
+    let backend = Arc::new(InstrumentedBackend::new(backend));
+
+    let auth = Authenticator::load(&config.auth).expect("Failed to load credentials");
+    if auth.is_none() {
+        tracing::warn!("No credentials configured, requests are not authenticated");
     }
-} 
\ No newline at end of file
+    let acl = config.acl_policy_file.as_deref().map(|path| {
+        assert!(auth.is_some(), "ACL_POLICY_FILE requires credentials to be configured");
+        let acl = Acl::load(path).expect("Failed to load ACL policy");
+        tracing::info!("Loaded {} ACL rules from {}", acl.policy().rules.len(), path);
+        Arc::new(acl)
+    });
+
+    AppState {
+        ring,
+        breakers,
+        legacy_values: config.legacy_values,
+        namespaces: Arc::new(Namespaces::new(backend.clone(), &config.namespaces)),
+        auth: auth.map(Arc::new),
+        acl,
+        ..AppState::new(backend)
+    }
+}
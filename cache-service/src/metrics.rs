@@ -0,0 +1,132 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    exponential_buckets, register_histogram_vec, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+// Metrics are registered in the default registry on first use.
+
+pub static HTTP_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Total number of HTTP requests",
+        &["method", "endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "Duration of HTTP requests in seconds",
+        &["method", "endpoint", "status"]
+    )
+    .unwrap()
+});
+
+pub static BACKEND_OPERATION_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cache_backend_operation_duration_seconds",
+        "Duration of cache backend operations in seconds",
+        &["operation", "outcome"],
+        exponential_buckets(0.0001, 2.0, 16).unwrap()
+    )
+    .unwrap()
+});
+
+pub static BACKEND_ERRORS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cache_backend_errors_total",
+        "Total number of failed cache backend operations",
+        &["operation", "kind"]
+    )
+    .unwrap()
+});
+
+pub static CACHE_LOOKUPS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cache_lookups_total",
+        "Total number of cache lookups by result",
+        &["result"]
+    )
+    .unwrap()
+});
+
+pub static TIER_LOOKUPS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "cache_tier_lookups_total",
+        "Total number of lookups in each tier of a tiered cache by result",
+        &["tier", "result"]
+    )
+    .unwrap()
+});
+
+pub static VALUE_SIZE_BYTES: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "cache_value_size_bytes",
+        "Size of values read from and written to the cache",
+        &["operation"],
+        exponential_buckets(64.0, 4.0, 10).unwrap()
+    )
+    .unwrap()
+});
+
+pub static REDIS_CONNECTION_STATE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "redis_connection_state",
+        "Current state of each Redis connection (1 for the active state)",
+        &["connection", "state"]
+    )
+    .unwrap()
+});
+
+pub static CIRCUIT_BREAKER_STATE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "circuit_breaker_state",
+        "Circuit breaker state: 0 closed, 1 open, 2 half-open",
+        &["name"]
+    )
+    .unwrap()
+});
+
+/// Records request count and latency per matched route and status.
+pub async fn track_http(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".into());
+    let start = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), endpoint.as_str(), status.as_str()];
+    HTTP_REQUESTS_TOTAL.with_label_values(&labels).inc();
+    HTTP_REQUEST_DURATION
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+    response
+}
+
+/// Serves all registered metrics in the Prometheus text format.
+pub async fn metrics_handler() -> Response {
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut body) {
+        tracing::error!("Failed to encode metrics: {}", e);
+    }
+    (
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        body,
+    )
+        .into_response()
+}
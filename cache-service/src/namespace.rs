@@ -0,0 +1,303 @@
+//! Namespaces isolating the keys of different callers.
+//!
+//! Keys of `/ns/:namespace/cache/...` routes are stored under their own
+//! prefix, with TTL and size limits of their namespace; the routes without a
+//! namespace address the `default` one.
+
+use crate::backend::{namespace_prefix, CacheBackend, NamespacedBackend, NAMESPACE_PREFIX};
+use crate::error::ApiError;
+use crate::AppState;
+use axum::extract::{FromRequestParts, RawPathParams};
+use axum::http::request::Parts;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name of the namespace of routes without one.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Longest TTL accepted in any namespace: ten years.
+pub const MAX_TTL: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// Limits of a namespace, configured in `NAMESPACES` as
+/// `name:default_ttl=5m,max_ttl=1h,max_value_bytes=65536`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceSettings {
+    pub name: String,
+    /// TTL of writes that do not ask for one.
+    pub default_ttl: Option<Duration>,
+    /// Longest TTL a key may have; keys cannot be persistent when set.
+    pub max_ttl: Option<Duration>,
+    /// Largest value accepted, in bytes as stored.
+    pub max_value_bytes: Option<usize>,
+}
+
+impl NamespaceSettings {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            default_ttl: None,
+            max_ttl: None,
+            max_value_bytes: None,
+        }
+    }
+}
+
+impl FromStr for NamespaceSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, options) = s.split_once(':').unwrap_or((s, ""));
+        let name = name.trim();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!("invalid namespace name {:?}", name));
+        }
+        let mut settings = NamespaceSettings::new(name);
+        for option in options.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            let (option, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("expected option=value, got {:?}", option))?;
+            let duration = || {
+                humantime::parse_duration(value)
+                    .map_err(|e| format!("invalid {} {:?}: {}", option, value, e))
+            };
+            match option {
+                "default_ttl" => settings.default_ttl = Some(duration()?),
+                "max_ttl" => settings.max_ttl = Some(duration()?),
+                "max_value_bytes" => {
+                    settings.max_value_bytes = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("invalid max_value_bytes {:?}: {}", value, e))?,
+                    )
+                }
+                other => return Err(format!("unknown namespace option {:?}", other)),
+            }
+        }
+        if let (Some(default), Some(max)) = (settings.default_ttl, settings.max_ttl) {
+            if default > max {
+                return Err(format!("default_ttl of namespace {} exceeds max_ttl", name));
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// A namespace a request addresses, extracted from the `:namespace` path
+/// segment.
+#[derive(Clone)]
+pub struct Namespace {
+    pub settings: Arc<NamespaceSettings>,
+    /// Backend confined to the keys of the namespace.
+    pub backend: Arc<dyn CacheBackend>,
+    /// Prefix of the keys of the namespace as stored; empty for `default`.
+    pub prefix: String,
+}
+
+impl Namespace {
+    pub fn name(&self) -> &str {
+        &self.settings.name
+    }
+
+    /// `key` as stored in the backend.
+    pub fn stored_key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Key of the namespace stored as `stored`, or `None` if it belongs to
+    /// another namespace.
+    pub fn local_key<'a>(&self, stored: &'a str) -> Option<&'a str> {
+        if self.prefix.is_empty() {
+            (!stored.starts_with(NAMESPACE_PREFIX)).then_some(stored)
+        } else {
+            stored.strip_prefix(self.prefix.as_str())
+        }
+    }
+
+    /// TTL of a write: the requested one, else the namespace default, else
+    /// the longest allowed. A zero TTL is rejected: the key would never be
+    /// readable.
+    pub fn write_ttl(&self, requested: Option<Duration>) -> Result<Option<Duration>, ApiError> {
+        if requested.is_some_and(|ttl| ttl.is_zero()) {
+            return Err(ApiError::InvalidTtl("ttl must be at least 1 second".into()));
+        }
+        let ttl = requested
+            .or(self.settings.default_ttl)
+            .or(self.settings.max_ttl);
+        self.check_ttl(ttl)?;
+        Ok(ttl)
+    }
+
+    /// Rejects TTLs beyond [`MAX_TTL`] or the namespace maximum, and
+    /// persistent keys when there is one.
+    pub fn check_ttl(&self, ttl: Option<Duration>) -> Result<(), ApiError> {
+        if ttl.is_some_and(|ttl| ttl > MAX_TTL) {
+            return Err(ApiError::InvalidTtl(format!(
+                "ttl must be at most {}",
+                humantime::format_duration(MAX_TTL)
+            )));
+        }
+        let Some(max) = self.settings.max_ttl else {
+            return Ok(());
+        };
+        match ttl {
+            Some(ttl) if ttl <= max => Ok(()),
+            Some(ttl) => Err(ApiError::BadRequest(format!(
+                "ttl of {} exceeds the maximum of {} in namespace {}",
+                humantime::format_duration(ttl),
+                humantime::format_duration(max),
+                self.name()
+            ))),
+            None => Err(ApiError::BadRequest(format!(
+                "keys in namespace {} must expire within {}",
+                self.name(),
+                humantime::format_duration(max)
+            ))),
+        }
+    }
+
+    /// Rejects values larger than the namespace allows.
+    pub fn check_size(&self, stored: &[u8]) -> Result<(), ApiError> {
+        match self.settings.max_value_bytes {
+            Some(limit) if stored.len() > limit => Err(ApiError::PayloadTooLarge(format!(
+                "value of {} bytes exceeds the limit of {} bytes in namespace {}",
+                stored.len(),
+                limit,
+                self.name()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Every configured namespace; `default` always exists.
+pub struct Namespaces {
+    namespaces: HashMap<String, Namespace>,
+}
+
+impl Namespaces {
+    pub fn new(backend: Arc<dyn CacheBackend>, settings: &[NamespaceSettings]) -> Self {
+        let mut namespaces = HashMap::new();
+        let default = NamespaceSettings::new(DEFAULT_NAMESPACE);
+        for settings in std::iter::once(&default).chain(settings) {
+            let (scoped, prefix) = if settings.name == DEFAULT_NAMESPACE {
+                (NamespacedBackend::root(backend.clone()), String::new())
+            } else {
+                let scoped = NamespacedBackend::new(backend.clone(), &settings.name);
+                (scoped, namespace_prefix(&settings.name))
+            };
+            let namespace = Namespace {
+                settings: Arc::new(settings.clone()),
+                backend: Arc::new(scoped),
+                prefix,
+            };
+            namespaces.insert(settings.name.clone(), namespace);
+        }
+        Self { namespaces }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Namespace> {
+        self.namespaces.get(name)
+    }
+}
+
+#[axum::async_trait]
+impl FromRequestParts<AppState> for Namespace {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, ApiError> {
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| ApiError::BadRequest(rejection.body_text()))?;
+        let name = params
+            .iter()
+            .find(|(param, _)| *param == "namespace")
+            .map_or(DEFAULT_NAMESPACE, |(_, name)| name);
+        state
+            .namespaces
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ApiError::NotFound(format!("namespace {:?} does not exist", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{MemoryBackend, MemoryLimits};
+
+    fn namespace(settings: &str) -> Namespace {
+        let backend = Arc::new(MemoryBackend::new(MemoryLimits {
+            max_entries: 10,
+            max_bytes: 1024,
+        }));
+        let settings: NamespaceSettings = settings.parse().unwrap();
+        let name = settings.name.clone();
+        Namespaces::new(backend, &[settings])
+            .get(&name)
+            .cloned()
+            .unwrap()
+    }
+
+    #[test]
+    fn parses_namespace_settings() {
+        let settings: NamespaceSettings = "billing: default_ttl=5m, max_ttl=1h, max_value_bytes=64"
+            .parse()
+            .unwrap();
+        assert_eq!(settings.name, "billing");
+        assert_eq!(settings.default_ttl, Some(Duration::from_secs(300)));
+        assert_eq!(settings.max_ttl, Some(Duration::from_secs(3600)));
+        assert_eq!(settings.max_value_bytes, Some(64));
+        assert_eq!("search".parse(), Ok(NamespaceSettings::new("search")));
+
+        assert!("bad name".parse::<NamespaceSettings>().is_err());
+        assert!("a:ttl=5m".parse::<NamespaceSettings>().is_err());
+        assert!("a:default_ttl=2h,max_ttl=1h"
+            .parse::<NamespaceSettings>()
+            .is_err());
+    }
+
+    #[test]
+    fn applies_ttl_limits() {
+        let billing = namespace("billing:default_ttl=5m,max_ttl=1h");
+        let minutes = |m: u64| Some(Duration::from_secs(m * 60));
+        assert_eq!(billing.write_ttl(None).unwrap(), minutes(5));
+        assert_eq!(billing.write_ttl(minutes(30)).unwrap(), minutes(30));
+        assert!(billing.write_ttl(minutes(90)).is_err());
+        assert!(billing.check_ttl(None).is_err());
+
+        let capped = namespace("capped:max_ttl=1h");
+        assert_eq!(capped.write_ttl(None).unwrap(), minutes(60));
+
+        let open = namespace("open");
+        assert_eq!(open.write_ttl(None).unwrap(), None);
+        assert!(open.check_ttl(None).is_ok());
+        assert!(matches!(
+            open.write_ttl(Some(Duration::ZERO)),
+            Err(ApiError::InvalidTtl(_))
+        ));
+        assert!(matches!(
+            open.write_ttl(Some(Duration::from_secs(u64::MAX))),
+            Err(ApiError::InvalidTtl(_))
+        ));
+        assert!(matches!(
+            open.check_ttl(Some(MAX_TTL + Duration::from_secs(1))),
+            Err(ApiError::InvalidTtl(_))
+        ));
+    }
+
+    #[test]
+    fn limits_value_size() {
+        let small = namespace("small:max_value_bytes=4");
+        assert!(small.check_size(b"1234").is_ok());
+        assert!(matches!(
+            small.check_size(b"12345"),
+            Err(ApiError::PayloadTooLarge(_))
+        ));
+    }
+}
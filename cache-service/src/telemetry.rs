@@ -0,0 +1,228 @@
+use axum::extract::{MatchedPath, Request};
+use axum::response::Response;
+use opentelemetry::trace::{SpanId, Status, TraceError, TracerProvider as _};
+use opentelemetry::{global, Key, KeyValue};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use serde_json::json;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::field::Empty;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+/// Where finished spans are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceExporter {
+    /// Spans are only used for log context.
+    None,
+    /// OTLP over HTTP to `OTEL_EXPORTER_OTLP_ENDPOINT`.
+    Otlp,
+    /// One JSON line per span on stdout.
+    Stdout,
+    /// One JSON line per span appended to a file.
+    File,
+}
+
+impl FromStr for TraceExporter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(TraceExporter::None),
+            "otlp" => Ok(TraceExporter::Otlp),
+            "stdout" => Ok(TraceExporter::Stdout),
+            "file" => Ok(TraceExporter::File),
+            other => Err(format!("unknown trace exporter {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TracingSettings {
+    pub exporter: TraceExporter,
+    /// Reported as `service.name` on every span.
+    pub service_name: String,
+    /// Destination of the `file` exporter.
+    pub file: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TelemetryError {
+    #[error("failed to open trace file {path}: {source}")]
+    File {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error(transparent)]
+    Exporter(#[from] TraceError),
+}
+
+/// Installs the global subscriber, logging to stdout and exporting spans
+/// as configured. The returned provider must be shut down to flush spans.
+pub fn init(settings: &TracingSettings) -> Result<Option<TracerProvider>, TelemetryError> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = match settings.exporter {
+        TraceExporter::None => None,
+        TraceExporter::Otlp => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .build()?;
+            Some(provider(exporter, settings))
+        }
+        TraceExporter::Stdout => Some(provider(
+            JsonLinesExporter::new(std::io::stdout()),
+            settings,
+        )),
+        TraceExporter::File => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&settings.file)
+                .map_err(|source| TelemetryError::File {
+                    path: settings.file.clone(),
+                    source,
+                })?;
+            Some(provider(JsonLinesExporter::new(file), settings))
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::EnvFilter::new(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+        ))
+        .with(tracing_subscriber::fmt::layer())
+        .with(provider.as_ref().map(|provider| {
+            tracing_opentelemetry::layer().with_tracer(provider.tracer("cache-service"))
+        }))
+        .init();
+    Ok(provider)
+}
+
+fn provider(exporter: impl SpanExporter + 'static, settings: &TracingSettings) -> TracerProvider {
+    TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            settings.service_name.clone(),
+        )]))
+        .build()
+}
+
+/// Creates the span of an HTTP request, continuing the caller's trace from
+/// its `traceparent` / `tracestate` headers.
+pub fn http_span(request: &Request) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", |path| path.as_str());
+    let span = tracing::info_span!(
+        "http_request",
+        otel.name = %format_args!("{} {}", request.method(), route),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %request.method(),
+        http.route = route,
+        url.path = request.uri().path(),
+        http.response.status_code = Empty,
+    );
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
+
+/// Records the response status on the request span.
+pub fn record_response(response: &Response, _latency: Duration, span: &Span) {
+    span.record("http.response.status_code", response.status().as_u16());
+    if response.status().is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Writes finished spans as JSON lines, to inspect traces without a collector.
+struct JsonLinesExporter {
+    writer: Box<dyn Write + Send + Sync>,
+    service_name: String,
+}
+
+impl JsonLinesExporter {
+    fn new(writer: impl Write + Send + Sync + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            service_name: String::new(),
+        }
+    }
+
+    fn write(&mut self, batch: Vec<SpanData>) -> std::io::Result<()> {
+        for span in batch {
+            let start = span
+                .start_time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            let duration = span
+                .end_time
+                .duration_since(span.start_time)
+                .unwrap_or_default();
+            let mut line = json!({
+                "service": self.service_name,
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "name": span.name,
+                "kind": format!("{:?}", span.span_kind).to_lowercase(),
+                "start_unix_nanos": start.as_nanos() as u64,
+                "duration_micros": duration.as_micros() as u64,
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|kv| (kv.key.to_string(), kv.value.to_string().into()))
+                    .collect::<serde_json::Map<_, _>>(),
+                "status": match span.status {
+                    Status::Unset => "unset",
+                    Status::Ok => "ok",
+                    Status::Error { .. } => "error",
+                },
+                "events": span.events.iter().map(|event| event.name.to_string()).collect::<Vec<_>>(),
+            });
+            if span.parent_span_id != SpanId::INVALID {
+                line["parent_span_id"] = span.parent_span_id.to_string().into();
+            }
+            writeln!(self.writer, "{}", line)?;
+        }
+        self.writer.flush()
+    }
+}
+
+impl fmt::Debug for JsonLinesExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonLinesExporter").finish_non_exhaustive()
+    }
+}
+
+impl SpanExporter for JsonLinesExporter {
+    fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let result = self
+            .write(batch)
+            .map_err(|e| TraceError::from(e.to_string()));
+        Box::pin(std::future::ready(result))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        if let Some(name) = resource.get(Key::new("service.name")) {
+            self.service_name = name.to_string();
+        }
+    }
+}
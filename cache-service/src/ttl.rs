@@ -0,0 +1,138 @@
+//! Bodies of the `/cache/:key/ttl` endpoints.
+
+use crate::backend::KeyTtl;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Body of `PUT /cache/:key/ttl`; exactly one field must be set.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TtlUpdate {
+    /// Human-readable duration such as `"90s"`, `"1h 30m"` or `"250ms"`.
+    pub ttl: Option<String>,
+    pub ttl_ms: Option<u64>,
+    /// RFC 3339 timestamp such as `"2024-05-01T12:00:00Z"`.
+    pub expires_at: Option<String>,
+    /// Unix time in milliseconds.
+    pub expires_at_ms: Option<u64>,
+}
+
+impl TtlUpdate {
+    /// Time left until the requested expiry. Expiry times in the past
+    /// yield zero, expiring the key right away.
+    pub fn remaining(&self, now: SystemTime) -> Result<Duration, String> {
+        let expires_at = match (&self.ttl, self.ttl_ms, &self.expires_at, self.expires_at_ms) {
+            (Some(ttl), None, None, None) => {
+                return humantime::parse_duration(ttl)
+                    .map_err(|e| format!("invalid ttl {:?}: {}", ttl, e))
+            }
+            (None, Some(ms), None, None) => return Ok(Duration::from_millis(ms)),
+            (None, None, Some(at), None) => humantime::parse_rfc3339_weak(at)
+                .map_err(|e| format!("invalid expires_at {:?}: {}", at, e))?,
+            (None, None, None, Some(ms)) => UNIX_EPOCH + Duration::from_millis(ms),
+            _ => {
+                return Err(
+                    "exactly one of ttl, ttl_ms, expires_at or expires_at_ms is required".into(),
+                )
+            }
+        };
+        Ok(expires_at.duration_since(now).unwrap_or_default())
+    }
+}
+
+/// Remaining lifetime of a key; all fields are `null` when it never expires.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TtlReport {
+    pub key: String,
+    pub ttl_ms: Option<u64>,
+    /// Human-readable form of `ttl_ms`.
+    pub ttl: Option<String>,
+    /// RFC 3339 form of `expires_at_ms`.
+    pub expires_at: Option<String>,
+    pub expires_at_ms: Option<u64>,
+}
+
+impl TtlReport {
+    /// Describes the lifetime of an existing key.
+    pub fn new(key: String, ttl: KeyTtl, now: SystemTime) -> Self {
+        let KeyTtl::Expires(remaining) = ttl else {
+            return Self {
+                key,
+                ttl_ms: None,
+                ttl: None,
+                expires_at: None,
+                expires_at_ms: None,
+            };
+        };
+        let remaining = Duration::from_millis(remaining.as_millis() as u64);
+        let expires_at = now + remaining;
+        Self {
+            key,
+            ttl_ms: Some(remaining.as_millis() as u64),
+            ttl: Some(humantime::format_duration(remaining).to_string()),
+            expires_at: Some(humantime::format_rfc3339_millis(expires_at).to_string()),
+            expires_at_ms: expires_at
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_millis() as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(body: &str) -> TtlUpdate {
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn accepts_relative_and_absolute_expiry() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let expected = Duration::from_millis(5_400_250);
+        for body in [
+            r#"{"ttl": "1h 30m 250ms"}"#,
+            r#"{"ttl_ms": 5400250}"#,
+            r#"{"expires_at": "2023-11-14T23:43:20.250Z"}"#,
+            r#"{"expires_at_ms": 1700005400250}"#,
+        ] {
+            assert_eq!(update(body).remaining(now).unwrap(), expected, "{}", body);
+        }
+        assert_eq!(
+            update(r#"{"expires_at_ms": 0}"#).remaining(now).unwrap(),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn rejects_ambiguous_or_invalid_updates() {
+        let now = SystemTime::now();
+        assert!(update("{}").remaining(now).is_err());
+        assert!(update(r#"{"ttl": "1m", "ttl_ms": 60000}"#)
+            .remaining(now)
+            .is_err());
+        assert!(update(r#"{"ttl": "soon"}"#).remaining(now).is_err());
+        assert!(serde_json::from_str::<TtlUpdate>(r#"{"seconds": 5}"#).is_err());
+    }
+
+    #[test]
+    fn reports_human_readable_lifetimes() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let report = TtlReport::new(
+            "a".into(),
+            KeyTtl::Expires(Duration::from_micros(90_500_900)),
+            now,
+        );
+        assert_eq!(report.ttl_ms, Some(90_500));
+        assert_eq!(report.ttl.as_deref(), Some("1m 30s 500ms"));
+        assert_eq!(
+            report.expires_at.as_deref(),
+            Some("2023-11-14T22:14:50.500Z")
+        );
+        assert_eq!(report.expires_at_ms, Some(1_700_000_090_500));
+
+        let persistent = TtlReport::new("a".into(), KeyTtl::Persistent, now);
+        assert_eq!(persistent.ttl_ms, None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Encoding of cached values.
+//!
+//! Every value carries a marker telling how it was written. JSON values
+//! set through the JSON API are stored as their serialized form:
+//!
+//! ```text
+//! "\0CSJ" | JSON
+//! ```
+//!
+//! Raw values written with `PUT /cache/:key` keep their media type in a
+//! header in front of the body:
+//!
+//! ```text
+//! "\0CSV" | content type length (u16, big endian) | content type | body
+//! ```
+//!
+//! Anything else is a plain string written before values were marked.
+
+use std::str::FromStr;
+
+const JSON_MAGIC: &[u8] = b"\0CSJ";
+const RAW_MAGIC: &[u8] = b"\0CSV";
+
+/// How unmarked strings written by earlier versions are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LegacyValues {
+    /// As a JSON string, like earlier versions did.
+    #[default]
+    String,
+    /// As the JSON structure a string holds, if it parses, else as a string.
+    Parse,
+}
+
+impl FromStr for LegacyValues {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "string" => Ok(LegacyValues::String),
+            "parse" => Ok(LegacyValues::Parse),
+            other => Err(format!("unknown legacy value mode {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ValueError {
+    #[error("value is not valid UTF-8")]
+    NotUtf8,
+    #[error("stored JSON is invalid: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+}
+
+/// A value read back from the backend.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredValue {
+    /// Serialized JSON written through the JSON API.
+    Json(Vec<u8>),
+    /// Raw bytes written with their media type.
+    Raw { content_type: String, body: Vec<u8> },
+    /// A plain string written before values were marked.
+    Legacy(Vec<u8>),
+}
+
+impl StoredValue {
+    /// The value's bytes without the marker.
+    pub fn body(&self) -> &[u8] {
+        match self {
+            StoredValue::Json(body) | StoredValue::Raw { body, .. } | StoredValue::Legacy(body) => {
+                body
+            }
+        }
+    }
+
+    /// The value as JSON. Raw values are returned as strings, so they must
+    /// be UTF-8.
+    pub fn into_json(self, legacy: LegacyValues) -> Result<serde_json::Value, ValueError> {
+        match self {
+            StoredValue::Json(body) => Ok(serde_json::from_slice(&body)?),
+            StoredValue::Raw { body, .. } => Ok(into_string(body)?.into()),
+            StoredValue::Legacy(body) => {
+                let text = into_string(body)?;
+                match legacy {
+                    LegacyValues::String => Ok(text.into()),
+                    LegacyValues::Parse => Ok(serde_json::from_str(&text).unwrap_or(text.into())),
+                }
+            }
+        }
+    }
+}
+
+fn into_string(body: Vec<u8>) -> Result<String, ValueError> {
+    String::from_utf8(body).map_err(|_| ValueError::NotUtf8)
+}
+
+/// Encodes a JSON value with its marker.
+pub fn encode_json(value: &serde_json::Value) -> Vec<u8> {
+    let mut bytes = JSON_MAGIC.to_vec();
+    serde_json::to_writer(&mut bytes, value).expect("JSON values always serialize");
+    bytes
+}
+
+/// Encodes a raw value together with its media type.
+///
+/// Content types longer than `u16::MAX` bytes are rejected by the caller.
+pub fn encode_raw(content_type: &str, body: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(RAW_MAGIC.len() + 2 + content_type.len() + body.len());
+    bytes.extend_from_slice(RAW_MAGIC);
+    bytes.extend_from_slice(&(content_type.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(content_type.as_bytes());
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+/// Decodes stored bytes; anything without a valid marker is a legacy string.
+pub fn decode(bytes: Vec<u8>) -> StoredValue {
+    if let Some(json) = bytes.strip_prefix(JSON_MAGIC) {
+        return StoredValue::Json(json.to_vec());
+    }
+    let Some(rest) = bytes.strip_prefix(RAW_MAGIC) else {
+        return StoredValue::Legacy(bytes);
+    };
+    let Some((len, rest)) = rest.split_first_chunk::<2>() else {
+        return StoredValue::Legacy(bytes);
+    };
+    let len = u16::from_be_bytes(*len) as usize;
+    match rest.get(..len).map(std::str::from_utf8) {
+        Some(Ok(content_type)) => StoredValue::Raw {
+            content_type: content_type.to_string(),
+            body: rest[len..].to_vec(),
+        },
+        _ => StoredValue::Legacy(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_values_keep_their_content_type() {
+        let body = [0u8, 159, 146, 150, 255];
+        let stored = decode(encode_raw("image/png", &body));
+        assert_eq!(
+            stored,
+            StoredValue::Raw {
+                content_type: "image/png".into(),
+                body: body.to_vec(),
+            }
+        );
+
+        let empty = decode(encode_raw("application/octet-stream", b""));
+        assert_eq!(empty.body(), b"");
+    }
+
+    #[test]
+    fn unmarked_strings_decode_as_legacy() {
+        assert_eq!(
+            decode(b"hello".to_vec()),
+            StoredValue::Legacy(b"hello".to_vec())
+        );
+        // A truncated header is not mistaken for a raw value.
+        assert_eq!(
+            decode(b"\0CSV\x00\x09image".to_vec()),
+            StoredValue::Legacy(b"\0CSV\x00\x09image".to_vec())
+        );
+    }
+
+    #[test]
+    fn json_values_keep_their_structure() {
+        let value = serde_json::json!({ "user": { "id": 7, "tags": ["a", "b"] } });
+        let stored = decode(encode_json(&value));
+        assert_eq!(stored.into_json(LegacyValues::String).unwrap(), value);
+
+        let text = decode(encode_json(&"{\"id\": 7}".into()));
+        assert_eq!(
+            text.into_json(LegacyValues::Parse).unwrap(),
+            serde_json::json!("{\"id\": 7}"),
+            "marked strings are never parsed"
+        );
+    }
+
+    #[test]
+    fn legacy_strings_follow_the_compatibility_mode() {
+        let legacy = || decode(b"{\"id\": 7}".to_vec());
+        assert_eq!(
+            legacy().into_json(LegacyValues::String).unwrap(),
+            serde_json::json!("{\"id\": 7}")
+        );
+        assert_eq!(
+            legacy().into_json(LegacyValues::Parse).unwrap(),
+            serde_json::json!({ "id": 7 })
+        );
+        assert_eq!(
+            decode(b"plain".to_vec())
+                .into_json(LegacyValues::Parse)
+                .unwrap(),
+            serde_json::json!("plain")
+        );
+    }
+}